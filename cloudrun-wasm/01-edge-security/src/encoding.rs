@@ -0,0 +1,231 @@
+//! Minimal, dependency-free transfer and charset decoding helpers.
+//!
+//! PII can hide inside base64 or quoted-printable encoded regions, or in a
+//! declared non-UTF-8 charset. These helpers decode such content to UTF-8
+//! for scanning and re-encode the redacted result back into the original
+//! representation, without pulling in crates the WASM runtime can't load.
+
+/// Decode a quoted-printable byte stream, returning `None` when no `=XX`
+/// escape or soft line break was found (i.e. the input wasn't actually QP).
+pub fn decode_quoted_printable(input: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut found_escape = false;
+    let mut i = 0;
+
+    while i < input.len() {
+        if input[i] != b'=' {
+            out.push(input[i]);
+            i += 1;
+            continue;
+        }
+
+        if i + 2 < input.len() && is_hex_digit(input[i + 1]) && is_hex_digit(input[i + 2]) {
+            out.push((hex_value(input[i + 1]) << 4) | hex_value(input[i + 2]));
+            i += 3;
+            found_escape = true;
+        } else if i + 2 < input.len() && input[i + 1] == b'\r' && input[i + 2] == b'\n' {
+            // Soft line break: "=\r\n" is removed entirely.
+            i += 3;
+            found_escape = true;
+        } else if i + 1 < input.len() && input[i + 1] == b'\n' {
+            i += 2;
+            found_escape = true;
+        } else {
+            out.push(input[i]);
+            i += 1;
+        }
+    }
+
+    if found_escape {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+/// Re-encode bytes as quoted-printable, escaping `=`, control bytes other
+/// than tab/newline/CR, and anything outside printable ASCII.
+pub fn encode_quoted_printable(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    for &b in input {
+        let needs_escape = b == b'=' || b >= 0x7f || (b < 0x20 && b != b'\t' && b != b'\n' && b != b'\r');
+        if needs_escape {
+            out.push(b'=');
+            out.push(hex_digit(b >> 4));
+            out.push(hex_digit(b & 0x0f));
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+/// Decode a base64 region, returning `None` when `input` (ignoring
+/// whitespace) isn't a plausible, well-formed base64 blob. A short minimum
+/// length guards against misreading incidental alphanumeric text as base64.
+pub fn decode_base64(input: &[u8]) -> Option<Vec<u8>> {
+    let filtered: Vec<u8> = input.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    if filtered.len() < 8 || filtered.len() % 4 != 0 {
+        return None;
+    }
+    if !filtered.iter().all(|&b| is_base64_alphabet(b)) {
+        return None;
+    }
+
+    let pad = filtered.iter().rev().take_while(|&&b| b == b'=').count();
+    if pad > 2 {
+        return None;
+    }
+    if filtered[..filtered.len() - pad].iter().any(|&b| b == b'=') {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(filtered.len() / 4 * 3);
+    for chunk in filtered.chunks(4) {
+        let b0 = base64_value(chunk[0])?;
+        let b1 = base64_value(chunk[1])?;
+        out.push((b0 << 2) | (b1 >> 4));
+
+        if chunk[2] != b'=' {
+            let b2 = base64_value(chunk[2])?;
+            out.push((b1 << 4) | (b2 >> 2));
+
+            if chunk[3] != b'=' {
+                let b3 = base64_value(chunk[3])?;
+                out.push((b2 << 6) | b3);
+            }
+        }
+    }
+    Some(out)
+}
+
+/// Re-encode bytes as standard (padded) base64.
+pub fn encode_base64(input: &[u8]) -> Vec<u8> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = Vec::with_capacity((input.len() + 2) / 3 * 4);
+
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+
+        out.push(ALPHABET[(b0 >> 2) as usize]);
+        out.push(ALPHABET[(((b0 & 0x03) << 4) | (b1 >> 4)) as usize]);
+        out.push(if chunk.len() > 1 {
+            ALPHABET[(((b1 & 0x0f) << 2) | (b2 >> 6)) as usize]
+        } else {
+            b'='
+        });
+        out.push(if chunk.len() > 2 { ALPHABET[(b2 & 0x3f) as usize] } else { b'=' });
+    }
+    out
+}
+
+/// Decode `bytes` into a `String` per `charset` (case-insensitive). Only
+/// UTF-8 and single-byte Latin-1-family charsets are recognized; anything
+/// else falls back to best-effort UTF-8, matching the matcher's existing
+/// "skip rather than fail" posture toward unrecognized configuration.
+pub fn decode_charset(bytes: &[u8], charset: Option<&str>) -> Option<String> {
+    if is_latin1_family(charset) {
+        return Some(bytes.iter().map(|&b| b as char).collect());
+    }
+    std::str::from_utf8(bytes).ok().map(|s| s.to_string())
+}
+
+/// Encode `s` back into bytes per `charset`, the inverse of [`decode_charset`].
+pub fn encode_charset(s: &str, charset: Option<&str>) -> Vec<u8> {
+    if is_latin1_family(charset) {
+        return s.chars().map(|c| c as u32 as u8).collect();
+    }
+    s.as_bytes().to_vec()
+}
+
+fn is_latin1_family(charset: Option<&str>) -> bool {
+    match charset {
+        Some(cs) => {
+            let cs = cs.to_lowercase();
+            cs.contains("8859-1") || cs.contains("latin1") || cs.contains("windows-1252") || cs.contains("cp1252")
+        }
+        None => false,
+    }
+}
+
+fn is_hex_digit(b: u8) -> bool {
+    b.is_ascii_hexdigit()
+}
+
+fn hex_value(b: u8) -> u8 {
+    match b {
+        b'0'..=b'9' => b - b'0',
+        b'a'..=b'f' => b - b'a' + 10,
+        b'A'..=b'F' => b - b'A' + 10,
+        _ => 0,
+    }
+}
+
+fn hex_digit(v: u8) -> u8 {
+    match v {
+        0..=9 => b'0' + v,
+        _ => b'A' + (v - 10),
+    }
+}
+
+fn base64_value(b: u8) -> Option<u8> {
+    match b {
+        b'A'..=b'Z' => Some(b - b'A'),
+        b'a'..=b'z' => Some(b - b'a' + 26),
+        b'0'..=b'9' => Some(b - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn is_base64_alphabet(b: u8) -> bool {
+    base64_value(b).is_some() || b == b'='
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trips() {
+        let original = b"SSN: 123-45-6789, ssn again";
+        let encoded = encode_base64(original);
+        let decoded = decode_base64(&encoded).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn base64_rejects_non_base64_text() {
+        assert!(decode_base64(b"Hello, World! This is plain text.").is_none());
+    }
+
+    #[test]
+    fn quoted_printable_round_trips() {
+        let original = b"Caf\xc3\xa9 costs =5 today";
+        let encoded = encode_quoted_printable(original);
+        let decoded = decode_quoted_printable(&encoded).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn quoted_printable_returns_none_without_escapes() {
+        assert!(decode_quoted_printable(b"plain ascii, nothing encoded").is_none());
+    }
+
+    #[test]
+    fn quoted_printable_handles_soft_line_break() {
+        let decoded = decode_quoted_printable(b"long line=\r\ncontinues").unwrap();
+        assert_eq!(decoded, b"long linecontinues");
+    }
+
+    #[test]
+    fn latin1_charset_round_trips_high_bytes() {
+        let bytes = vec![0xE9, 0x20, b'o', b'k']; // é ok
+        let decoded = decode_charset(&bytes, Some("ISO-8859-1")).unwrap();
+        assert_eq!(decoded, "\u{e9} ok");
+        assert_eq!(encode_charset(&decoded, Some("iso-8859-1")), bytes);
+    }
+}
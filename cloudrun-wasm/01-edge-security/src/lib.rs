@@ -12,14 +12,18 @@
 //! - Location: Response Path
 //! - Callback: `on_http_response_body`
 
+mod encoding;
+mod mini_regex;
 mod patterns;
+mod security_headers;
 
 use log::{debug, info, warn};
 use proxy_wasm::traits::{Context, HttpContext, RootContext};
 use proxy_wasm::types::{Action, ContextType, LogLevel};
 use serde::Deserialize;
 
-use patterns::{PiiPatternMatcher, RedactionResult};
+use patterns::{CustomReplacement, DecodeConfig, PiiPattern, PiiPatternMatcher, RedactionMode, StreamingRedactor};
+use security_headers::SecurityHeaderPolicy;
 
 // =============================================================================
 // Configuration
@@ -43,6 +47,75 @@ struct PluginConfig {
     /// Maximum body size to scan (bytes)
     #[serde(default = "default_max_body_size")]
     max_body_size_bytes: usize,
+
+    /// Transfer/charset decoding applied before scanning
+    #[serde(default)]
+    decode: DecodeSettings,
+
+    /// Response security-header hardening applied regardless of scrubbing.
+    #[serde(default)]
+    security_headers: SecurityHeaderPolicy,
+
+    /// Operator-defined patterns (e.g. a passport number or internal API key
+    /// format) layered on top of the built-in detectors.
+    #[serde(default)]
+    custom_patterns: Vec<CustomPatternConfig>,
+}
+
+/// A single operator-supplied pattern, as it appears in the Envoy config.
+#[derive(Debug, Clone, Deserialize)]
+struct CustomPatternConfig {
+    /// Name reported in `matched_patterns` when this pattern fires.
+    name: String,
+    /// Pattern source, compiled by the mini-regex engine (see
+    /// `mini_regex` for supported syntax).
+    pattern: String,
+    #[serde(flatten)]
+    replacement: CustomReplacementConfig,
+}
+
+/// How a matched custom pattern is rewritten, tagged by `mode`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+enum CustomReplacementConfig {
+    /// Replace the whole match with `value`, e.g. "[API KEY REDACTED]".
+    Fixed { value: String },
+    /// Mask every alphanumeric character except the last `keep`.
+    PreserveLast { keep: usize },
+}
+
+impl From<&CustomPatternConfig> for PiiPattern {
+    fn from(config: &CustomPatternConfig) -> Self {
+        let replacement = match &config.replacement {
+            CustomReplacementConfig::Fixed { value } => CustomReplacement::Fixed(value.clone()),
+            CustomReplacementConfig::PreserveLast { keep } => CustomReplacement::PreserveLast(*keep),
+        };
+        PiiPattern::Custom {
+            name: config.name.clone(),
+            pattern: config.pattern.clone(),
+            replacement,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DecodeSettings {
+    /// Recognize and decode base64/quoted-printable regions before scanning
+    #[serde(default)]
+    enabled: bool,
+    /// Declared charset (e.g. "iso-8859-1") to interpret decoded bytes as,
+    /// instead of UTF-8
+    #[serde(default)]
+    declared_charset: Option<String>,
+}
+
+impl Default for DecodeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            declared_charset: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -55,6 +128,22 @@ struct PatternConfig {
     email: bool,
     #[serde(default)]
     phone_us: bool,
+    #[serde(default)]
+    ip: bool,
+
+    /// Require a Luhn-valid checksum before redacting a credit-card-shaped
+    /// match, so order IDs/tracking numbers aren't treated as PII. Strict
+    /// PCI use cases should leave this on; noisy test data can disable it.
+    #[serde(default = "default_true")]
+    credit_card_luhn: bool,
+
+    /// How matched values are rewritten: "mask" (default, today's fixed
+    /// replacement), "partial" (preserve length/structure, e.g.
+    /// "****-****-****-1111"), or "tokenize" (deterministic opaque token
+    /// per matched value, for correlating redacted logs without exposing
+    /// the value).
+    #[serde(default)]
+    redaction_mode: RedactionMode,
 }
 
 impl Default for PatternConfig {
@@ -64,6 +153,9 @@ impl Default for PatternConfig {
             ssn: true,
             email: true,
             phone_us: false,
+            ip: false,
+            credit_card_luhn: true,
+            redaction_mode: RedactionMode::default(),
         }
     }
 }
@@ -87,6 +179,9 @@ impl Default for PluginConfig {
             patterns: PatternConfig::default(),
             bypass_paths: vec!["/health".to_string(), "/metrics".to_string()],
             max_body_size_bytes: default_max_body_size(),
+            decode: DecodeSettings::default(),
+            security_headers: SecurityHeaderPolicy::default(),
+            custom_patterns: Vec::new(),
         }
     }
 }
@@ -163,7 +258,28 @@ struct PiiScrubberHttp {
     config: PluginConfig,
     request_path: String,
     should_scrub: bool,
-    accumulated_body_size: usize,
+    /// Total raw response-body bytes seen so far, across every
+    /// `on_http_response_body` call. Each call's `get_http_response_body`/
+    /// `set_http_response_body` only ever address that call's own buffer
+    /// (offset `0`..`body_size`), so this is used purely to enforce
+    /// `max_body_size_bytes` - never as a read/write offset.
+    body_offset: usize,
+    /// Built once scrubbing is confirmed in `on_http_response_headers`;
+    /// `None` until then or once scrubbing has been called off.
+    scrub: Option<ScrubMode>,
+}
+
+/// How `on_http_response_body` scans the body. Decode-aware scanning
+/// (base64/quoted-printable/charset, see `PiiPatternMatcher::redact`) needs a
+/// complete encoded region to decode, which a chunk boundary can split
+/// anywhere - so it can't run incrementally the way plain-text scanning via
+/// `StreamingRedactor` can, and instead buffers the whole body.
+enum ScrubMode {
+    /// `decode.enabled = false`: scrub each chunk as it arrives.
+    Streaming(StreamingRedactor),
+    /// `decode.enabled = true`: accumulate the whole body and scrub it once,
+    /// at `end_of_stream`.
+    Buffered { matcher: PiiPatternMatcher, body: Vec<u8> },
 }
 
 impl PiiScrubberHttp {
@@ -173,7 +289,8 @@ impl PiiScrubberHttp {
             config,
             request_path: String::new(),
             should_scrub: true,
-            accumulated_body_size: 0,
+            body_offset: 0,
+            scrub: None,
         }
     }
 
@@ -192,16 +309,43 @@ impl PiiScrubberHttp {
         false
     }
 
-    /// Perform PII redaction on the body
-    fn redact_pii(&self, body: &[u8]) -> RedactionResult {
-        let matcher = PiiPatternMatcher::new(
+    /// Build a matcher from the current config.
+    fn build_matcher(&self) -> PiiPatternMatcher {
+        PiiPatternMatcher::new(
             self.config.patterns.credit_card,
             self.config.patterns.ssn,
             self.config.patterns.email,
             self.config.patterns.phone_us,
-        );
+            self.config.patterns.ip,
+        )
+        .with_credit_card_luhn(self.config.patterns.credit_card_luhn)
+        .with_redaction_mode(self.config.patterns.redaction_mode)
+        .with_decode_config(DecodeConfig {
+            enabled: self.config.decode.enabled,
+            declared_charset: self.config.decode.declared_charset.clone(),
+        })
+        .with_custom_patterns(self.config.custom_patterns.iter().map(PiiPattern::from).collect())
+    }
 
-        matcher.redact(body)
+    /// Inject the configured security-header set, unless this is an
+    /// upgraded (e.g. websocket) connection, where adding framing headers
+    /// would break the proxied stream.
+    fn apply_security_headers(&self) {
+        let connection = self
+            .get_http_response_header("connection")
+            .or_else(|| self.get_http_request_header("connection"));
+        let upgrade = self
+            .get_http_response_header("upgrade")
+            .or_else(|| self.get_http_request_header("upgrade"));
+
+        if security_headers::is_upgraded_connection(connection.as_deref(), upgrade.as_deref()) {
+            debug!("[{}] Skipping security headers for upgraded connection", self.context_id);
+            return;
+        }
+
+        for (name, value) in security_headers::headers_to_add(&self.config.security_headers) {
+            self.add_http_response_header(name, &value);
+        }
     }
 }
 
@@ -227,10 +371,12 @@ impl HttpContext for PiiScrubberHttp {
 
     fn on_http_response_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
         info!("DIAG: on_http_response_headers called for context_id={}", self.context_id);
-        
+
+        self.apply_security_headers();
+
         // Add debug header to prove WASM is running
         self.add_http_response_header("X-WASM-Active", "true");
-        
+
         if !self.should_scrub {
             self.add_http_response_header("X-WASM-Scrub", "bypassed");
             return Action::Continue;
@@ -264,6 +410,14 @@ impl HttpContext for PiiScrubberHttp {
         // Remove content-length header as we may modify the body
         self.set_http_response_header("content-length", None);
         self.add_http_response_header("X-WASM-Scrub", "will-scrub");
+        self.scrub = Some(if self.config.decode.enabled {
+            ScrubMode::Buffered {
+                matcher: self.build_matcher(),
+                body: Vec::new(),
+            }
+        } else {
+            ScrubMode::Streaming(StreamingRedactor::new(self.build_matcher()))
+        });
 
         Action::Continue
     }
@@ -271,50 +425,85 @@ impl HttpContext for PiiScrubberHttp {
     fn on_http_response_body(&mut self, body_size: usize, end_of_stream: bool) -> Action {
         info!("DIAG: on_http_response_body called for context_id={}, body_size={}, end_of_stream={}",
               self.context_id, body_size, end_of_stream);
-        
+
         if !self.should_scrub {
             info!("DIAG: skipping scrub (should_scrub=false)");
             return Action::Continue;
         }
 
-        // Track accumulated body size for later retrieval
-        self.accumulated_body_size += body_size;
-
-        // Only process when we have the complete body
-        if !end_of_stream {
-            return Action::Continue;
-        }
-
-        // Check size limit
-        if self.accumulated_body_size > self.config.max_body_size_bytes {
-            warn!("[{}] Body too large ({} bytes), passing through",
-                  self.context_id, self.accumulated_body_size);
+        self.body_offset += body_size;
+
+        // Bail out of scrubbing (but keep streaming the rest through
+        // untouched) once the body has grown past the configured limit -
+        // this is the streaming equivalent of the old accumulated-size
+        // check, since chunked responses have no declared content-length.
+        if self.body_offset > self.config.max_body_size_bytes {
+            warn!("[{}] Body too large ({} bytes), passing through unscrubbed",
+                  self.context_id, self.body_offset);
+            self.should_scrub = false;
+            self.scrub = None;
             return Action::Continue;
         }
 
-        // Get the full accumulated body
-        let full_body = match self.get_http_response_body(0, self.accumulated_body_size) {
-            Some(body) => body,
+        // `body_size` is this call's own chunk, addressed at offset 0 - the
+        // host hands the wasm module one buffer per invocation, not a
+        // stream-wide cumulative view.
+        let chunk = match self.get_http_response_body(0, body_size) {
+            Some(chunk) => chunk,
             None => {
-                warn!("[{}] Failed to get response body", self.context_id);
+                warn!("[{}] Failed to get response body chunk", self.context_id);
                 return Action::Continue;
             }
         };
 
-        // Perform PII redaction
-        let result = self.redact_pii(&full_body);
-
-        if result.redacted {
-            info!("[{}] Redacted {} PII patterns: {:?}",
-                  self.context_id, result.match_count, result.matched_patterns);
-
-            // NOTE: Cannot add headers in on_http_response_body - headers already sent
-            // Headers must be added in on_http_response_headers callback
-            
-            // Replace the response body with redacted content
-            self.set_http_response_body(0, full_body.len(), &result.content);
-        } else {
-            debug!("[{}] No PII patterns found in response", self.context_id);
+        // NOTE: Cannot add headers in on_http_response_body - headers already sent
+        // Headers must be added in on_http_response_headers callback
+        let scrub = self
+            .scrub
+            .as_mut()
+            .expect("scrub mode is set whenever should_scrub is true");
+
+        match scrub {
+            ScrubMode::Streaming(redactor) => {
+                let committed = redactor.process_chunk(&chunk, end_of_stream);
+
+                // Always replace the whole of this call's buffer, even when
+                // `committed` is empty: anything left unreplaced here would
+                // be forwarded downstream as-is, leaking the portion
+                // `process_chunk` is holding back in its carry until a later
+                // chunk resolves it.
+                self.set_http_response_body(0, body_size, &committed);
+
+                if end_of_stream {
+                    if redactor.match_count() > 0 {
+                        info!("[{}] Redacted {} PII patterns: {:?}",
+                              self.context_id, redactor.match_count(), redactor.matched_patterns());
+                    } else {
+                        debug!("[{}] No PII patterns found in response", self.context_id);
+                    }
+                }
+            }
+            ScrubMode::Buffered { matcher, body } => {
+                body.extend_from_slice(&chunk);
+
+                if end_of_stream {
+                    let result = matcher.redact(body);
+                    // The whole redacted body lands in this, the final,
+                    // call's buffer - every earlier call in `Buffered` mode
+                    // replaced its own chunk with nothing, so this is the
+                    // first and only data the host actually forwards.
+                    self.set_http_response_body(0, body_size, &result.content);
+
+                    if result.match_count > 0 {
+                        info!("[{}] Redacted {} PII patterns: {:?}",
+                              self.context_id, result.match_count, result.matched_patterns);
+                    } else {
+                        debug!("[{}] No PII patterns found in response", self.context_id);
+                    }
+                } else {
+                    self.set_http_response_body(0, body_size, &[]);
+                }
+            }
         }
 
         Action::Continue
@@ -347,6 +536,12 @@ mod tests {
         assert!(config.patterns.ssn);
         assert!(config.patterns.email);
         assert!(!config.patterns.phone_us);
+        assert!(!config.patterns.ip);
+        assert!(config.patterns.credit_card_luhn);
+        assert_eq!(config.patterns.redaction_mode, RedactionMode::Mask);
+        assert!(config.security_headers.x_content_type_options);
+        assert!(config.security_headers.x_frame_options);
+        assert!(!config.security_headers.content_security_policy);
     }
 
     #[test]
@@ -368,9 +563,60 @@ mod tests {
             ..Default::default()
         };
         let ctx = PiiScrubberHttp::new(1, config);
-        
+
         assert!(ctx.should_bypass_path("/api/internal/debug"));
         assert!(ctx.should_bypass_path("/api/internal/metrics"));
         assert!(!ctx.should_bypass_path("/api/user"));
     }
+
+    #[test]
+    fn test_response_body_streaming_catches_ipv6_split_across_chunks() {
+        // `on_http_response_body` wraps exactly this matcher in a
+        // StreamingRedactor and feeds it one get_http_response_body() buffer
+        // per call; an IPv6 address split across two such buffers must
+        // still be redacted by the time the stream is flushed.
+        let config = PluginConfig {
+            patterns: PatternConfig {
+                ip: true,
+                ..PatternConfig::default()
+            },
+            ..Default::default()
+        };
+        let ctx = PiiScrubberHttp::new(1, config);
+        let mut streaming = StreamingRedactor::new(ctx.build_matcher());
+
+        let mut out = streaming.process_chunk(b"Host: 2001:0db8:0000:0000:0000:ff00:", false);
+        out.extend(streaming.process_chunk(b"0042:8329 connected", true));
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Host: [IPv6 REDACTED] connected"
+        );
+        assert_eq!(streaming.match_count(), 1);
+    }
+
+    #[test]
+    fn test_custom_patterns_config_reaches_the_matcher() {
+        // custom_patterns must actually be reachable from Envoy JSON config,
+        // not just from with_custom_patterns() calls in a test - otherwise
+        // PiiPattern::Custom is dead from an operator's perspective.
+        let json = br#"{
+            "custom_patterns": [
+                {"name": "api_key", "pattern": "sk-[A-Za-z0-9]{8}", "mode": "fixed", "value": "[API KEY REDACTED]"}
+            ]
+        }"#;
+        let config: PluginConfig = serde_json::from_slice(json).expect("valid config");
+        assert_eq!(config.custom_patterns.len(), 1);
+
+        let ctx = PiiScrubberHttp::new(1, config);
+        let matcher = ctx.build_matcher();
+        let result = matcher.redact(b"Key: sk-ABCD1234 in use");
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("api_key"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Key: [API KEY REDACTED] in use"
+        );
+    }
 }
\ No newline at end of file
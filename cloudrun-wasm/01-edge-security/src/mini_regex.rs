@@ -0,0 +1,475 @@
+//! Minimal no-dependency regex engine for user-defined PII patterns.
+//!
+//! NOTE: Same constraint as `patterns.rs` - the `regex` crate panics in the
+//! GCP Service Extensions WASM runtime, so custom PII patterns are compiled
+//! and matched with hand-rolled, dependency-free code.
+//!
+//! Supported syntax:
+//! - Literals
+//! - Escape classes: `\d` `\w` `\s`
+//! - Character classes: `[A-Za-z0-9]`, negated with `[^...]`
+//! - Quantifiers: `*`, `+`, `?`, `{m}`, `{m,n}`
+//! - Anchors: `^`, `$`
+//! - Grouped alternation: `(a|b)`
+//!
+//! A pattern compiles once into a token sequence (`Elem`), and matching is a
+//! bounded recursive-backtracking walk over a `&[char]` slice. Every match
+//! attempt carries a step budget so a pathological pattern can only ever
+//! fail to match - it can never spin the WASM module to termination.
+
+/// A single atom in the compiled token sequence.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(char),
+    Digit,
+    Word,
+    Space,
+    Class(Vec<ClassItem>, bool),
+    Group(Vec<Vec<Elem>>),
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+}
+
+/// A token plus its repetition bounds (min..=max, max `None` means unbounded).
+#[derive(Debug, Clone, PartialEq)]
+struct Elem {
+    node: Node,
+    min: usize,
+    max: Option<usize>,
+}
+
+/// A compiled pattern, ready to be matched at any offset in a `&[char]` slice.
+#[derive(Debug, Clone)]
+pub struct CompiledPattern {
+    elems: Vec<Elem>,
+    max_steps: u32,
+}
+
+/// Default backtracking step budget for a single match attempt.
+pub const DEFAULT_MAX_BACKTRACK_STEPS: u32 = 10_000;
+
+impl CompiledPattern {
+    /// Compile `pattern` into a token sequence, bounding every match attempt
+    /// to `max_steps` backtracking steps.
+    pub fn compile(pattern: &str, max_steps: u32) -> Result<Self, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let alts = parse_alternation(&chars, &mut pos)?;
+        if pos != chars.len() {
+            return Err(format!("unexpected character at offset {}", pos));
+        }
+        if alts.len() != 1 {
+            // Top level alternation (e.g. "a|b" with no surrounding parens)
+            // collapses to a single implicit group.
+            return Ok(Self {
+                elems: vec![Elem {
+                    node: Node::Group(alts),
+                    min: 1,
+                    max: Some(1),
+                }],
+                max_steps,
+            });
+        }
+        Ok(Self {
+            elems: alts.into_iter().next().unwrap(),
+            max_steps,
+        })
+    }
+
+    /// True when the pattern can only ever match starting at offset 0.
+    fn anchored_start(&self) -> bool {
+        matches!(self.elems.first(), Some(Elem { node: Node::Start, .. }))
+    }
+
+    /// Upper bound on how many characters a single match of this pattern can
+    /// span, or `None` if some element repeats without an upper bound (e.g.
+    /// `\d+` or `\w*`) and so the match length can't be bounded in advance.
+    /// Used to size the carry-over window a streaming caller must hold
+    /// across chunk boundaries so a match can't straddle one undetected.
+    pub fn max_match_len(&self) -> Option<usize> {
+        max_elems_len(&self.elems)
+    }
+
+    /// Try to match starting exactly at `pos`, returning the end offset on
+    /// success. Each call gets a fresh backtracking budget.
+    pub fn try_match_at(&self, chars: &[char], pos: usize) -> Option<usize> {
+        let mut budget: i64 = self.max_steps as i64;
+        match_seq(&self.elems, chars, pos, &mut budget)
+    }
+
+    /// Scan forward from `from` looking for the first match, honoring a
+    /// leading `^` anchor (which restricts the search to offset 0).
+    pub fn find_from(&self, chars: &[char], from: usize) -> Option<(usize, usize)> {
+        let anchored = self.anchored_start();
+        let mut i = from;
+        loop {
+            if anchored && i != 0 {
+                return None;
+            }
+            if i > chars.len() {
+                return None;
+            }
+            if let Some(end) = self.try_match_at(chars, i) {
+                return Some((i, end));
+            }
+            if anchored || i >= chars.len() {
+                return None;
+            }
+            i += 1;
+        }
+    }
+}
+
+fn matches_zero_width(node: &Node) -> bool {
+    matches!(node, Node::Start | Node::End)
+}
+
+/// Sum the per-element max match lengths, short-circuiting to `None` as
+/// soon as any element (or, for a `Group`, its longest alternative) has no
+/// upper bound.
+fn max_elems_len(elems: &[Elem]) -> Option<usize> {
+    let mut total = 0usize;
+    for elem in elems {
+        let node_max = match &elem.node {
+            Node::Start | Node::End => 0,
+            Node::Group(alts) => {
+                let mut longest = 0usize;
+                for alt in alts {
+                    longest = longest.max(max_elems_len(alt)?);
+                }
+                longest
+            }
+            Node::Literal(_) | Node::Digit | Node::Word | Node::Space | Node::Class(..) => 1,
+        };
+        total = total.checked_add(node_max.checked_mul(elem.max?)?)?;
+    }
+    Some(total)
+}
+
+fn node_matches_char(node: &Node, c: char) -> bool {
+    match node {
+        Node::Literal(l) => *l == c,
+        Node::Digit => c.is_ascii_digit(),
+        Node::Word => c.is_alphanumeric() || c == '_',
+        Node::Space => c.is_whitespace(),
+        Node::Class(items, negated) => {
+            let hit = items.iter().any(|item| match item {
+                ClassItem::Char(ic) => *ic == c,
+                ClassItem::Range(lo, hi) => c >= *lo && c <= *hi,
+            });
+            hit != *negated
+        }
+        Node::Start | Node::End | Node::Group(_) => false,
+    }
+}
+
+/// Try to match `node` once at `pos`, returning the position after the match.
+fn match_node_once(node: &Node, chars: &[char], pos: usize, budget: &mut i64) -> Option<usize> {
+    if *budget <= 0 {
+        return None;
+    }
+    *budget -= 1;
+    match node {
+        Node::Start => {
+            if pos == 0 {
+                Some(pos)
+            } else {
+                None
+            }
+        }
+        Node::End => {
+            if pos == chars.len() {
+                Some(pos)
+            } else {
+                None
+            }
+        }
+        Node::Group(alts) => {
+            for alt in alts {
+                if let Some(end) = match_seq(alt, chars, pos, budget) {
+                    return Some(end);
+                }
+            }
+            None
+        }
+        _ => {
+            if pos < chars.len() && node_matches_char(node, chars[pos]) {
+                Some(pos + 1)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Match a single (possibly repeated) element, then the rest of the
+/// sequence, backtracking from the greedy repeat count down to `min`.
+fn match_elem(elem: &Elem, chars: &[char], pos: usize, rest: &[Elem], budget: &mut i64) -> Option<usize> {
+    let max = elem.max.unwrap_or(usize::MAX);
+    let mut positions = vec![pos];
+    let mut cur = pos;
+
+    while positions.len() - 1 < max {
+        if *budget <= 0 {
+            return None;
+        }
+        match match_node_once(&elem.node, chars, cur, budget) {
+            Some(next) => {
+                let zero_width = matches_zero_width(&elem.node) && next == cur;
+                cur = next;
+                positions.push(cur);
+                if zero_width {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+
+    if positions.len() - 1 < elem.min {
+        return None;
+    }
+
+    for k in (elem.min..positions.len()).rev() {
+        if *budget <= 0 {
+            return None;
+        }
+        if let Some(end) = match_seq(rest, chars, positions[k], budget) {
+            return Some(end);
+        }
+    }
+    None
+}
+
+fn match_seq(elems: &[Elem], chars: &[char], pos: usize, budget: &mut i64) -> Option<usize> {
+    if *budget <= 0 {
+        return None;
+    }
+    *budget -= 1;
+    match elems.split_first() {
+        None => Some(pos),
+        Some((elem, rest)) => match_elem(elem, chars, pos, rest, budget),
+    }
+}
+
+// =============================================================================
+// Parsing
+// =============================================================================
+
+fn parse_alternation(chars: &[char], pos: &mut usize) -> Result<Vec<Vec<Elem>>, String> {
+    let mut alts = vec![parse_sequence(chars, pos)?];
+    while *pos < chars.len() && chars[*pos] == '|' {
+        *pos += 1;
+        alts.push(parse_sequence(chars, pos)?);
+    }
+    Ok(alts)
+}
+
+fn parse_sequence(chars: &[char], pos: &mut usize) -> Result<Vec<Elem>, String> {
+    let mut seq = Vec::new();
+    while *pos < chars.len() && chars[*pos] != '|' && chars[*pos] != ')' {
+        seq.push(parse_elem(chars, pos)?);
+    }
+    Ok(seq)
+}
+
+fn parse_elem(chars: &[char], pos: &mut usize) -> Result<Elem, String> {
+    let node = parse_atom(chars, pos)?;
+    let (min, max) = parse_quantifier(chars, pos)?;
+    Ok(Elem { node, min, max })
+}
+
+fn parse_quantifier(chars: &[char], pos: &mut usize) -> Result<(usize, Option<usize>), String> {
+    if *pos >= chars.len() {
+        return Ok((1, Some(1)));
+    }
+    match chars[*pos] {
+        '*' => {
+            *pos += 1;
+            Ok((0, None))
+        }
+        '+' => {
+            *pos += 1;
+            Ok((1, None))
+        }
+        '?' => {
+            *pos += 1;
+            Ok((0, Some(1)))
+        }
+        '{' => {
+            let start = *pos;
+            *pos += 1;
+            let m = parse_number(chars, pos)?;
+            if *pos < chars.len() && chars[*pos] == '}' {
+                *pos += 1;
+                return Ok((m, Some(m)));
+            }
+            if *pos < chars.len() && chars[*pos] == ',' {
+                *pos += 1;
+                let n = parse_number(chars, pos)?;
+                if *pos < chars.len() && chars[*pos] == '}' {
+                    *pos += 1;
+                    return Ok((m, Some(n)));
+                }
+            }
+            // Not a well-formed counted quantifier - treat the `{` as literal.
+            *pos = start;
+            Ok((1, Some(1)))
+        }
+        _ => Ok((1, Some(1))),
+    }
+}
+
+fn parse_number(chars: &[char], pos: &mut usize) -> Result<usize, String> {
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+        *pos += 1;
+    }
+    if *pos == start {
+        return Err(format!("expected a number at offset {}", start));
+    }
+    chars[start..*pos]
+        .iter()
+        .collect::<String>()
+        .parse::<usize>()
+        .map_err(|e| e.to_string())
+}
+
+fn parse_atom(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    if *pos >= chars.len() {
+        return Err("unexpected end of pattern".to_string());
+    }
+    match chars[*pos] {
+        '^' => {
+            *pos += 1;
+            Ok(Node::Start)
+        }
+        '$' => {
+            *pos += 1;
+            Ok(Node::End)
+        }
+        '(' => {
+            *pos += 1;
+            let alts = parse_alternation(chars, pos)?;
+            if *pos >= chars.len() || chars[*pos] != ')' {
+                return Err("unterminated group".to_string());
+            }
+            *pos += 1;
+            Ok(Node::Group(alts))
+        }
+        '[' => parse_class(chars, pos),
+        '\\' => {
+            *pos += 1;
+            if *pos >= chars.len() {
+                return Err("dangling escape".to_string());
+            }
+            let c = chars[*pos];
+            *pos += 1;
+            match c {
+                'd' => Ok(Node::Digit),
+                'w' => Ok(Node::Word),
+                's' => Ok(Node::Space),
+                other => Ok(Node::Literal(other)),
+            }
+        }
+        c => {
+            *pos += 1;
+            Ok(Node::Literal(c))
+        }
+    }
+}
+
+fn parse_class(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    *pos += 1; // consume '['
+    let mut negated = false;
+    if *pos < chars.len() && chars[*pos] == '^' {
+        negated = true;
+        *pos += 1;
+    }
+    let mut items = Vec::new();
+    while *pos < chars.len() && chars[*pos] != ']' {
+        let c = if chars[*pos] == '\\' {
+            *pos += 1;
+            if *pos >= chars.len() {
+                return Err("dangling escape in class".to_string());
+            }
+            let escaped = chars[*pos];
+            *pos += 1;
+            escaped
+        } else {
+            let literal = chars[*pos];
+            *pos += 1;
+            literal
+        };
+
+        if *pos + 1 < chars.len() && chars[*pos] == '-' && chars[*pos + 1] != ']' {
+            *pos += 1; // consume '-'
+            let hi = chars[*pos];
+            *pos += 1;
+            items.push(ClassItem::Range(c, hi));
+        } else {
+            items.push(ClassItem::Char(c));
+        }
+    }
+    if *pos >= chars.len() || chars[*pos] != ']' {
+        return Err("unterminated character class".to_string());
+    }
+    *pos += 1; // consume ']'
+    Ok(Node::Class(items, negated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, haystack: &str) -> Option<(usize, usize)> {
+        let compiled = CompiledPattern::compile(pattern, DEFAULT_MAX_BACKTRACK_STEPS).unwrap();
+        let chars: Vec<char> = haystack.chars().collect();
+        compiled.find_from(&chars, 0)
+    }
+
+    #[test]
+    fn matches_literal() {
+        assert_eq!(find("abc", "xxabcxx"), Some((2, 5)));
+    }
+
+    #[test]
+    fn matches_digit_class() {
+        assert_eq!(find(r"\d{3}-\d{2}", "id 123-45 end"), Some((3, 9)));
+    }
+
+    #[test]
+    fn matches_char_class_with_range_and_negation() {
+        assert_eq!(find("[A-Z]{2}[0-9]+", "ref AB1234!"), Some((4, 10)));
+        assert!(find("[^0-9]+", "12345").is_none());
+    }
+
+    #[test]
+    fn matches_alternation_group() {
+        assert_eq!(find("(cat|dog)s?", "I have dogs"), Some((7, 11)));
+    }
+
+    #[test]
+    fn respects_anchors() {
+        assert_eq!(find("^abc", "abcdef"), Some((0, 3)));
+        assert!(find("^abc", "xabcdef").is_none());
+        assert!(find(r"\d+$", "abc123").is_some());
+        assert!(find(r"^\d+$", "abc123").is_none());
+    }
+
+    #[test]
+    fn pathological_pattern_is_bounded_not_panicking() {
+        // Classic catastrophic-backtracking shape: (a+)+b against a string
+        // with no trailing 'b'. The step budget must make this fail fast
+        // instead of exploring exponentially many paths.
+        let compiled = CompiledPattern::compile("(a+)+b", 5_000).unwrap();
+        let haystack: Vec<char> = "a".repeat(40).chars().collect();
+        assert_eq!(compiled.find_from(&haystack, 0), None);
+    }
+}
@@ -9,10 +9,89 @@
 
 use std::collections::HashSet;
 
+use serde::Deserialize;
+
+use crate::encoding;
+use crate::mini_regex::{CompiledPattern, DEFAULT_MAX_BACKTRACK_STEPS};
+
+// =============================================================================
+// Custom Patterns
+// =============================================================================
+
+/// A user-supplied pattern registered on top of the built-in detectors.
+#[derive(Debug, Clone)]
+pub enum PiiPattern {
+    /// A pattern compiled by the no-dependency mini-regex engine, e.g. a
+    /// passport number, IBAN, or API key format not covered by the built-ins.
+    Custom {
+        name: String,
+        pattern: String,
+        replacement: CustomReplacement,
+    },
+}
+
+/// How a matched custom pattern is rewritten in the output.
+#[derive(Debug, Clone)]
+pub enum CustomReplacement {
+    /// Replace the whole match with a fixed string, e.g. "[API KEY REDACTED]".
+    Fixed(String),
+    /// Mask every alphanumeric character except the last `keep` of the
+    /// match, leaving separators untouched - mirrors `redact_credit_cards`
+    /// and `redact_phone_us`.
+    PreserveLast(usize),
+}
+
+/// A compiled custom pattern ready to be matched against input text.
+struct CompiledCustom {
+    name: String,
+    prog: CompiledPattern,
+    replacement: CustomReplacement,
+}
+
+// =============================================================================
+// Encoding Detection
+// =============================================================================
+
+/// Controls whether `redact` looks through a transfer/charset encoding
+/// before scanning, so PII hidden in a base64 or quoted-printable body
+/// isn't missed entirely.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeConfig {
+    /// Whether to attempt decoding at all.
+    pub enabled: bool,
+    /// A declared charset (e.g. "iso-8859-1") to interpret decoded bytes
+    /// as, instead of UTF-8.
+    pub declared_charset: Option<String>,
+}
+
 // =============================================================================
 // Pattern Matcher
 // =============================================================================
 
+/// How a matched value is rewritten in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionMode {
+    /// Replace the match with the pattern's usual fixed/partial mask, e.g.
+    /// "XXXX-XXXX-XXXX-1111" or "[EMAIL REDACTED]". The default.
+    Mask,
+    /// Keep the last few characters of the match and mask the rest with
+    /// `*`, preserving the original length and separator structure, e.g.
+    /// "****-****-****-1111" - keeps JSON field widths and downstream
+    /// parsers happy.
+    Partial,
+    /// Replace the match with a deterministic opaque token derived from a
+    /// keyed hash of the matched value, so the same PII maps to the same
+    /// token within a response without exposing the original value.
+    Tokenize,
+}
+
+impl Default for RedactionMode {
+    fn default() -> Self {
+        RedactionMode::Mask
+    }
+}
+
 /// Result of a redaction operation
 #[derive(Debug, Clone)]
 pub struct RedactionResult {
@@ -24,6 +103,11 @@ pub struct RedactionResult {
     pub matched_patterns: HashSet<String>,
     /// The sanitized content
     pub content: Vec<u8>,
+    /// The byte region of the *original* input that was decoded (base64 or
+    /// quoted-printable) before scanning, if any. `None` means the input
+    /// was scanned as-is; callers can use this to tell whether a match came
+    /// from plaintext or an encoded segment.
+    pub decoded_region: Option<(usize, usize)>,
 }
 
 /// Matcher for PII patterns with configurable pattern enablement
@@ -32,6 +116,16 @@ pub struct PiiPatternMatcher {
     enable_ssn: bool,
     enable_email: bool,
     enable_phone_us: bool,
+    enable_ip: bool,
+    /// Require a candidate credit-card number to pass the Luhn checksum
+    /// before redacting it, so order IDs/tracking numbers that merely look
+    /// card-shaped aren't treated as PII. Defaults to `true`.
+    credit_card_luhn: bool,
+    /// How matched values are rewritten; see [`RedactionMode`]. Defaults to
+    /// `Mask`.
+    redaction_mode: RedactionMode,
+    custom: Vec<CompiledCustom>,
+    decode: DecodeConfig,
 }
 
 impl PiiPatternMatcher {
@@ -41,29 +135,82 @@ impl PiiPatternMatcher {
         enable_ssn: bool,
         enable_email: bool,
         enable_phone_us: bool,
+        enable_ip: bool,
     ) -> Self {
         Self {
             enable_credit_card,
             enable_ssn,
             enable_email,
             enable_phone_us,
+            enable_ip,
+            credit_card_luhn: true,
+            redaction_mode: RedactionMode::default(),
+            custom: Vec::new(),
+            decode: DecodeConfig::default(),
+        }
+    }
+
+    /// Enable scanning through a transfer/charset encoding layer.
+    pub fn with_decode_config(mut self, decode: DecodeConfig) -> Self {
+        self.decode = decode;
+        self
+    }
+
+    /// Override whether candidate credit-card numbers must pass the Luhn
+    /// checksum to be redacted (on by default).
+    pub fn with_credit_card_luhn(mut self, enabled: bool) -> Self {
+        self.credit_card_luhn = enabled;
+        self
+    }
+
+    /// Override how matched values are rewritten (default `Mask`).
+    pub fn with_redaction_mode(mut self, mode: RedactionMode) -> Self {
+        self.redaction_mode = mode;
+        self
+    }
+
+    /// Register custom patterns on top of the built-in detectors. Patterns
+    /// that fail to compile are skipped rather than surfaced as an error,
+    /// since a malformed operator-supplied pattern must never take down the
+    /// WASM module.
+    pub fn with_custom_patterns(mut self, patterns: Vec<PiiPattern>) -> Self {
+        for p in patterns {
+            let PiiPattern::Custom {
+                name,
+                pattern,
+                replacement,
+            } = p;
+            if let Ok(prog) = CompiledPattern::compile(&pattern, DEFAULT_MAX_BACKTRACK_STEPS) {
+                self.custom.push(CompiledCustom {
+                    name,
+                    prog,
+                    replacement,
+                });
+            }
         }
+        self
     }
 
     /// Create a matcher with all patterns enabled
     #[allow(dead_code)]
     pub fn all() -> Self {
-        Self::new(true, true, true, true)
+        Self::new(true, true, true, true, true)
     }
 
-    /// Create a matcher with default patterns (no phone)
+    /// Create a matcher with default patterns (no phone, no IP)
     #[allow(dead_code)]
     pub fn default_patterns() -> Self {
-        Self::new(true, true, true, false)
+        Self::new(true, true, true, false, false)
     }
 
     /// Perform redaction on the input bytes
     pub fn redact(&self, input: &[u8]) -> RedactionResult {
+        if self.decode.enabled {
+            if let Some(result) = self.redact_encoded(input) {
+                return result;
+            }
+        }
+
         // Convert to string for processing
         let input_str = match std::str::from_utf8(input) {
             Ok(s) => s,
@@ -74,17 +221,74 @@ impl PiiPatternMatcher {
                     match_count: 0,
                     matched_patterns: HashSet::new(),
                     content: input.to_vec(),
+                    decoded_region: None,
                 };
             }
         };
 
-        let mut result = input_str.to_string();
+        let (content, match_count, matched_patterns) = self.apply_patterns(input_str);
+
+        RedactionResult {
+            redacted: match_count > 0,
+            match_count,
+            matched_patterns,
+            content: content.into_bytes(),
+            decoded_region: None,
+        }
+    }
+
+    /// Try to decode `input` as a base64 or quoted-printable region (per
+    /// `self.decode`), scan the decoded text, and re-encode the redacted
+    /// result back into the original representation. Returns `None` when
+    /// decoding isn't applicable, so the caller falls back to scanning
+    /// `input` directly as plain text.
+    fn redact_encoded(&self, input: &[u8]) -> Option<RedactionResult> {
+        let charset = self.decode.declared_charset.as_deref();
+
+        if let Some(decoded) = encoding::decode_quoted_printable(input) {
+            if let Some(text) = encoding::decode_charset(&decoded, charset) {
+                let (content, match_count, matched_patterns) = self.apply_patterns(&text);
+                if match_count > 0 {
+                    let bytes = encoding::encode_charset(&content, charset);
+                    return Some(RedactionResult {
+                        redacted: true,
+                        match_count,
+                        matched_patterns,
+                        content: encoding::encode_quoted_printable(&bytes),
+                        decoded_region: Some((0, input.len())),
+                    });
+                }
+            }
+        }
+
+        if let Some(decoded) = encoding::decode_base64(input) {
+            if let Some(text) = encoding::decode_charset(&decoded, charset) {
+                let (content, match_count, matched_patterns) = self.apply_patterns(&text);
+                if match_count > 0 {
+                    let bytes = encoding::encode_charset(&content, charset);
+                    return Some(RedactionResult {
+                        redacted: true,
+                        match_count,
+                        matched_patterns,
+                        content: encoding::encode_base64(&bytes),
+                        decoded_region: Some((0, input.len())),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Run every enabled built-in and custom pattern over `input`, returning
+    /// the redacted text, total match count, and matched pattern names.
+    fn apply_patterns(&self, input: &str) -> (String, u32, HashSet<String>) {
+        let mut result = input.to_string();
         let mut match_count: u32 = 0;
         let mut matched_patterns = HashSet::new();
 
-        // Apply each enabled pattern
         if self.enable_credit_card {
-            let (new_result, count) = redact_credit_cards(&result);
+            let (new_result, count) = redact_credit_cards(&result, self.credit_card_luhn, self.redaction_mode);
             if count > 0 {
                 matched_patterns.insert("credit_card".to_string());
                 match_count += count;
@@ -93,7 +297,7 @@ impl PiiPatternMatcher {
         }
 
         if self.enable_ssn {
-            let (new_result, count) = redact_ssn(&result);
+            let (new_result, count) = redact_ssn(&result, self.redaction_mode);
             if count > 0 {
                 matched_patterns.insert("ssn".to_string());
                 match_count += count;
@@ -102,7 +306,7 @@ impl PiiPatternMatcher {
         }
 
         if self.enable_email {
-            let (new_result, count) = redact_email(&result);
+            let (new_result, count) = redact_email(&result, self.redaction_mode);
             if count > 0 {
                 matched_patterns.insert("email".to_string());
                 match_count += count;
@@ -111,7 +315,7 @@ impl PiiPatternMatcher {
         }
 
         if self.enable_phone_us {
-            let (new_result, count) = redact_phone_us(&result);
+            let (new_result, count) = redact_phone_us(&result, self.redaction_mode);
             if count > 0 {
                 matched_patterns.insert("phone_us".to_string());
                 match_count += count;
@@ -119,12 +323,25 @@ impl PiiPatternMatcher {
             }
         }
 
-        RedactionResult {
-            redacted: match_count > 0,
-            match_count,
-            matched_patterns,
-            content: result.into_bytes(),
+        if self.enable_ip {
+            let (new_result, count) = redact_ip(&result, self.redaction_mode);
+            if count > 0 {
+                matched_patterns.insert("ip".to_string());
+                match_count += count;
+                result = new_result;
+            }
+        }
+
+        for custom in &self.custom {
+            let (new_result, count) = redact_custom(custom, &result);
+            if count > 0 {
+                matched_patterns.insert(custom.name.clone());
+                match_count += count;
+                result = new_result;
+            }
         }
+
+        (result, match_count, matched_patterns)
     }
 }
 
@@ -138,8 +355,9 @@ fn is_digit(c: char) -> bool {
 }
 
 /// Redact credit card numbers in format: 1234-5678-9012-3456
-/// Replaces with: XXXX-XXXX-XXXX-3456 (preserves last 4 digits)
-fn redact_credit_cards(input: &str) -> (String, u32) {
+/// Replaces with: XXXX-XXXX-XXXX-3456 (preserves last 4 digits), or per
+/// `mode` if not `Mask`.
+fn redact_credit_cards(input: &str, luhn_enabled: bool, mode: RedactionMode) -> (String, u32) {
     let mut result = String::with_capacity(input.len());
     let chars: Vec<char> = input.chars().collect();
     let mut i = 0;
@@ -149,11 +367,10 @@ fn redact_credit_cards(input: &str) -> (String, u32) {
         // Try to match credit card pattern: DDDD-DDDD-DDDD-DDDD
         if i + 18 < chars.len() {
             let potential_cc: String = chars[i..i + 19].iter().collect();
-            if is_credit_card_format(&potential_cc) {
-                // Extract last 4 digits and redact
+            if is_credit_card_format(&potential_cc) && (!luhn_enabled || passes_luhn(&potential_cc)) {
                 let last_four = &potential_cc[15..19];
-                result.push_str("XXXX-XXXX-XXXX-");
-                result.push_str(last_four);
+                let default_mask = format!("XXXX-XXXX-XXXX-{}", last_four);
+                result.push_str(&render_replacement(&chars[i..i + 19], mode, 4, &default_mask));
                 i += 19;
                 count += 1;
                 continue;
@@ -167,10 +384,11 @@ fn redact_credit_cards(input: &str) -> (String, u32) {
                 // Check it's not part of a longer number
                 let before_ok = i == 0 || !is_digit(chars[i - 1]);
                 let after_ok = i + 16 >= chars.len() || !is_digit(chars[i + 16]);
-                if before_ok && after_ok {
+                let potential_cc: String = chars[i..i + 16].iter().collect();
+                if before_ok && after_ok && (!luhn_enabled || passes_luhn(&potential_cc)) {
                     let last_four: String = chars[i + 12..i + 16].iter().collect();
-                    result.push_str("XXXXXXXXXXXX");
-                    result.push_str(&last_four);
+                    let default_mask = format!("XXXXXXXXXXXX{}", last_four);
+                    result.push_str(&render_replacement(&chars[i..i + 16], mode, 4, &default_mask));
                     i += 16;
                     count += 1;
                     continue;
@@ -210,9 +428,41 @@ fn is_credit_card_format(s: &str) -> bool {
     true
 }
 
+/// Luhn checksum, used to gate credit-card detection so digit groups that
+/// merely look card-shaped (order IDs, tracking numbers, timestamps) aren't
+/// redacted as PII. Strips separators, then from the rightmost digit walks
+/// left doubling every second digit (subtracting 9 when the doubled value
+/// exceeds 9), and checks the digit sum is divisible by 10.
+fn passes_luhn(s: &str) -> bool {
+    let digits: Vec<u32> = s.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.is_empty() {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(idx, &digit)| {
+            if idx % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+
+    sum % 10 == 0
+}
+
 /// Redact SSN numbers in format: 123-45-6789
-/// Replaces with: XXX-XX-XXXX
-fn redact_ssn(input: &str) -> (String, u32) {
+/// Replaces with: XXX-XX-XXXX, or per `mode` if not `Mask`.
+fn redact_ssn(input: &str, mode: RedactionMode) -> (String, u32) {
     let mut result = String::with_capacity(input.len());
     let chars: Vec<char> = input.chars().collect();
     let mut i = 0;
@@ -227,7 +477,7 @@ fn redact_ssn(input: &str) -> (String, u32) {
                 let before_ok = i == 0 || !chars[i - 1].is_alphanumeric();
                 let after_ok = i + 11 >= chars.len() || !chars[i + 11].is_alphanumeric();
                 if before_ok && after_ok {
-                    result.push_str("XXX-XX-XXXX");
+                    result.push_str(&render_replacement(&chars[i..i + 11], mode, 4, "XXX-XX-XXXX"));
                     i += 11;
                     count += 1;
                     continue;
@@ -267,81 +517,156 @@ fn is_ssn_format(s: &str) -> bool {
     true
 }
 
-/// Redact email addresses
-/// Replaces with: [EMAIL REDACTED]
-fn redact_email(input: &str) -> (String, u32) {
-    let mut result = String::with_capacity(input.len());
+/// Redact email addresses matched against a structured RFC 5322 `addr-spec`
+/// (`local-part "@" domain`), instead of the old "scan alphanumeric runs"
+/// heuristic.
+/// Replaces with: [EMAIL REDACTED], or per `mode` if not `Mask`.
+fn redact_email(input: &str, mode: RedactionMode) -> (String, u32) {
     let chars: Vec<char> = input.chars().collect();
-    let mut i = 0;
+    let mut result = String::with_capacity(input.len());
     let mut count = 0;
+    let mut last_copied = 0;
+    let mut i = 0;
 
     while i < chars.len() {
-        // Look for @ symbol
         if chars[i] == '@' {
-            // Find the start of the email (local part)
-            let start = find_email_start(&chars, i);
-            // Find the end of the email (domain part)
-            let end = find_email_end(&chars, i);
-
-            if start < i && end > i + 1 {
-                // Valid email found - check for valid domain with dot
-                let domain: String = chars[i + 1..end].iter().collect();
-                if domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.') {
-                    // Remove the local part we already added
-                    let to_remove = i - start;
-                    for _ in 0..to_remove {
-                        result.pop();
-                    }
-                    result.push_str("[EMAIL REDACTED]");
-                    i = end;
-                    count += 1;
-                    continue;
-                }
+            if let Some((start, end)) = match_addr_spec_at(&chars, i) {
+                result.extend(&chars[last_copied..start]);
+                result.push_str(&render_replacement(&chars[start..end], mode, 4, "[EMAIL REDACTED]"));
+                last_copied = end;
+                i = end;
+                count += 1;
+                continue;
             }
         }
-
-        result.push(chars[i]);
         i += 1;
     }
 
+    result.extend(&chars[last_copied..]);
     (result, count)
 }
 
-/// Find the start index of an email address (before @)
-fn find_email_start(chars: &[char], at_pos: usize) -> usize {
+/// `atext` per RFC 5322: alphanumerics (including non-ASCII letters, so a
+/// multi-byte UTF-8 local part is handled correctly since we operate over
+/// `&[char]` rather than bytes) plus a fixed set of specials.
+fn is_atext(c: char) -> bool {
+    c.is_alphanumeric()
+        || matches!(
+            c,
+            '!' | '#' | '$' | '%' | '&' | '\'' | '*' | '+' | '-' | '/' | '=' | '?' | '^' | '_' | '`' | '{' | '|' | '}' | '~'
+        )
+}
+
+/// Match `addr-spec` anchored so the `@` lands at `at_pos`, returning the
+/// precise `(start, end)` offsets of the whole address so the caller can
+/// splice the replacement without rewinding already-pushed output.
+fn match_addr_spec_at(chars: &[char], at_pos: usize) -> Option<(usize, usize)> {
+    let start = match_local_part_end_at(chars, at_pos)?;
+    let end = parse_domain_forward(chars, at_pos + 1)?;
+    Some((start, end))
+}
+
+/// Find the start of the local part ending exactly at `at_pos` (the `@`),
+/// as either a quoted-string or a dot-atom.
+fn match_local_part_end_at(chars: &[char], at_pos: usize) -> Option<usize> {
     if at_pos == 0 {
-        return at_pos;
+        return None;
+    }
+    if chars[at_pos - 1] == '"' {
+        return match_quoted_local_backward(chars, at_pos);
     }
 
+    // Expand backward over the widest plausible dot-atom candidate, then
+    // forward-validate with the real grammar so a leading/trailing dot or
+    // an empty label (e.g. "foo.@bar") is rejected rather than accepted.
     let mut start = at_pos;
-    for j in (0..at_pos).rev() {
-        let c = chars[j];
-        if c.is_alphanumeric() || c == '.' || c == '_' || c == '%' || c == '+' || c == '-' {
-            start = j;
-        } else {
-            break;
+    while start > 0 && (is_atext(chars[start - 1]) || chars[start - 1] == '.') {
+        start -= 1;
+    }
+    for s in start..at_pos {
+        if let Some(end) = parse_dot_atom_forward(chars, s) {
+            if end == at_pos {
+                return Some(s);
+            }
         }
     }
-    start
+    None
 }
 
-/// Find the end index of an email address (after @)
-fn find_email_end(chars: &[char], at_pos: usize) -> usize {
-    let mut end = at_pos + 1;
-    for j in (at_pos + 1)..chars.len() {
-        let c = chars[j];
-        if c.is_alphanumeric() || c == '.' || c == '-' {
-            end = j + 1;
-        } else {
-            break;
+/// Find the opening quote of a quoted-string local part whose closing quote
+/// sits at `chars[at_pos - 1]`, rejecting an escaped quote or empty content.
+fn match_quoted_local_backward(chars: &[char], at_pos: usize) -> Option<usize> {
+    let close = at_pos - 1;
+    if close == 0 {
+        return None;
+    }
+    let mut q = close;
+    while q > 0 {
+        q -= 1;
+        if chars[q] == '"' {
+            let escaped = q > 0 && chars[q - 1] == '\\';
+            if escaped {
+                continue;
+            }
+            return if close > q + 1 { Some(q) } else { None };
+        }
+    }
+    None
+}
+
+/// Match a run of 1+ `atext` characters starting at `pos`.
+fn parse_atext_run_forward(chars: &[char], pos: usize) -> Option<usize> {
+    let mut p = pos;
+    while p < chars.len() && is_atext(chars[p]) {
+        p += 1;
+    }
+    if p == pos {
+        None
+    } else {
+        Some(p)
+    }
+}
+
+/// Match `dot-atom-text = 1*atext *("." 1*atext)` - no leading, trailing,
+/// or doubled dots.
+fn parse_dot_atom_forward(chars: &[char], pos: usize) -> Option<usize> {
+    let mut p = parse_atext_run_forward(chars, pos)?;
+    while p < chars.len() && chars[p] == '.' {
+        match parse_atext_run_forward(chars, p + 1) {
+            Some(next) => p = next,
+            None => break,
         }
     }
-    end
+    Some(p)
+}
+
+/// Match `domain`: either a dot-atom with at least one interior dot (so a
+/// bare `bar` with no TLD is rejected), or a bracketed address-literal.
+fn parse_domain_forward(chars: &[char], pos: usize) -> Option<usize> {
+    if pos < chars.len() && chars[pos] == '[' {
+        let interior_start = pos + 1;
+        let mut p = interior_start;
+        while p < chars.len() && chars[p] != ']' && !chars[p].is_whitespace() {
+            p += 1;
+        }
+        if p >= chars.len() || chars[p] != ']' || p == interior_start {
+            return None;
+        }
+        return Some(p + 1);
+    }
+
+    let end = parse_dot_atom_forward(chars, pos)?;
+    let label_count = chars[pos..end].iter().filter(|c| **c == '.').count() + 1;
+    if label_count < 2 {
+        return None;
+    }
+    Some(end)
 }
 
 /// Redact US phone numbers in format: 555-123-4567 or 555.123.4567
-/// Replaces with: (XXX) XXX-4567 (preserves last 4 digits)
-fn redact_phone_us(input: &str) -> (String, u32) {
+/// Replaces with: (XXX) XXX-4567 (preserves last 4 digits), or per `mode`
+/// if not `Mask`.
+fn redact_phone_us(input: &str, mode: RedactionMode) -> (String, u32) {
     let mut result = String::with_capacity(input.len());
     let chars: Vec<char> = input.chars().collect();
     let mut i = 0;
@@ -357,8 +682,8 @@ fn redact_phone_us(input: &str) -> (String, u32) {
                 let after_ok = i + 12 >= chars.len() || !chars[i + 12].is_alphanumeric();
                 if before_ok && after_ok {
                     let last_four = &potential_phone[8..12];
-                    result.push_str("(XXX) XXX-");
-                    result.push_str(last_four);
+                    let default_mask = format!("(XXX) XXX-{}", last_four);
+                    result.push_str(&render_replacement(&chars[i..i + 12], mode, 4, &default_mask));
                     i += 12;
                     count += 1;
                     continue;
@@ -404,115 +729,873 @@ fn is_phone_format(s: &str) -> bool {
     true
 }
 
-// =============================================================================
-// Tests
-// =============================================================================
+/// Redact IPv4 and IPv6 addresses.
+/// IPv4 replaces with: XXX.XXX.XXX.0 (preserves nothing but the octet shape)
+/// IPv6 replaces with: [IPv6 REDACTED]
+/// Both replace per `mode` instead if not `Mask`.
+fn redact_ip(input: &str, mode: RedactionMode) -> (String, u32) {
+    let mut result = String::with_capacity(input.len());
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+    let mut count = 0;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    while i < chars.len() {
+        let before_ok = i == 0 || !is_word_boundary_char(chars[i - 1]);
 
-    #[test]
-    fn test_credit_card_redaction() {
-        let matcher = PiiPatternMatcher::default_patterns();
-        let input = b"Card: 4111-1111-1111-1111";
-        let result = matcher.redact(input);
+        if before_ok {
+            if let Some(end) = match_ipv6(&chars, i) {
+                let after_ok = end >= chars.len() || !is_word_boundary_char(chars[end]);
+                if after_ok {
+                    result.push_str(&render_replacement(&chars[i..end], mode, 4, "[IPv6 REDACTED]"));
+                    i = end;
+                    count += 1;
+                    continue;
+                }
+            }
 
-        assert!(result.redacted);
-        assert_eq!(result.match_count, 1);
-        assert!(result.matched_patterns.contains("credit_card"));
-        assert_eq!(
-            String::from_utf8_lossy(&result.content),
-            "Card: XXXX-XXXX-XXXX-1111"
-        );
+            if let Some(end) = match_ipv4(&chars, i) {
+                let after_ok = end >= chars.len() || !is_word_boundary_char(chars[end]);
+                if after_ok {
+                    result.push_str(&render_replacement(&chars[i..end], mode, 4, "XXX.XXX.XXX.0"));
+                    i = end;
+                    count += 1;
+                    continue;
+                }
+            }
+        }
+
+        result.push(chars[i]);
+        i += 1;
     }
 
-    #[test]
-    fn test_credit_card_no_dash_redaction() {
-        let matcher = PiiPatternMatcher::default_patterns();
-        let input = b"Card: 4111111111111111";
-        let result = matcher.redact(input);
+    (result, count)
+}
 
-        assert!(result.redacted);
-        assert_eq!(result.match_count, 1);
-        assert!(result.matched_patterns.contains("credit_card"));
-        assert_eq!(
-            String::from_utf8_lossy(&result.content),
-            "Card: XXXXXXXXXXXX1111"
-        );
-    }
+/// A char that can be part of an alphanumeric token, so it can't directly
+/// precede or follow an IP address literal.
+fn is_word_boundary_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
 
-    #[test]
-    fn test_ssn_redaction() {
-        let matcher = PiiPatternMatcher::default_patterns();
-        let input = b"SSN: 123-45-6789";
-        let result = matcher.redact(input);
+/// Match `DDD.DDD.DDD.DDD` where each group is a valid 0-255 decimal octet
+/// with no unnecessary leading zero. Returns the end offset on success.
+fn match_ipv4(chars: &[char], start: usize) -> Option<usize> {
+    let mut pos = start;
+    for group in 0..4 {
+        if group > 0 {
+            if pos >= chars.len() || chars[pos] != '.' {
+                return None;
+            }
+            pos += 1;
+        }
+        pos = match_ipv4_octet(chars, pos)?;
+    }
+    Some(pos)
+}
 
-        assert!(result.redacted);
-        assert_eq!(result.match_count, 1);
-        assert!(result.matched_patterns.contains("ssn"));
-        assert_eq!(
-            String::from_utf8_lossy(&result.content),
-            "SSN: XXX-XX-XXXX"
-        );
+/// Match one 1-3 digit decimal octet in 0..=255, rejecting values like
+/// `256` and redundant leading zeros (e.g. `01`), and return the end offset.
+fn match_ipv4_octet(chars: &[char], start: usize) -> Option<usize> {
+    let mut end = start;
+    while end < chars.len() && end < start + 3 && is_digit(chars[end]) {
+        end += 1;
     }
+    if end == start {
+        return None;
+    }
+    let digits: String = chars[start..end].iter().collect();
+    if digits.len() > 1 && digits.starts_with('0') {
+        return None;
+    }
+    let value: u32 = digits.parse().ok()?;
+    if value > 255 {
+        return None;
+    }
+    Some(end)
+}
 
-    #[test]
-    fn test_email_redaction() {
-        let matcher = PiiPatternMatcher::default_patterns();
-        let input = b"Email: john.doe@example.com";
-        let result = matcher.redact(input);
+/// Match a literal IPv6 address: 2-8 groups of 1-4 hex digits separated by
+/// `:`, at most one `::` compression, optional bracketing, and an optional
+/// trailing embedded IPv4 tail (e.g. `::ffff:192.168.0.1`).
+fn match_ipv6(chars: &[char], start: usize) -> Option<usize> {
+    let bracketed = chars.get(start) == Some(&'[');
+    let mut pos = if bracketed { start + 1 } else { start };
+    let body_start = pos;
+
+    let mut groups = 0;
+    let mut saw_compression = false;
+    let mut last_was_colon = false;
+
+    loop {
+        if pos < chars.len() && chars[pos] == ':' {
+            if pos + 1 < chars.len() && chars[pos + 1] == ':' {
+                if saw_compression {
+                    return None;
+                }
+                saw_compression = true;
+                pos += 2;
+                last_was_colon = true;
+                continue;
+            }
+            if groups == 0 && !saw_compression {
+                // A lone leading ':' with no compression is not valid.
+                return None;
+            }
+            pos += 1;
+            last_was_colon = true;
+            continue;
+        }
 
-        assert!(result.redacted);
-        assert_eq!(result.match_count, 1);
-        assert!(result.matched_patterns.contains("email"));
-        assert_eq!(
-            String::from_utf8_lossy(&result.content),
-            "Email: [EMAIL REDACTED]"
-        );
-    }
+        // Try an embedded IPv4 tail first (only valid as the last segment).
+        if let Some(v4_end) = match_ipv4(chars, pos) {
+            pos = v4_end;
+            groups += 2;
+            last_was_colon = false;
+            break;
+        }
 
-    #[test]
-    fn test_phone_redaction_when_enabled() {
-        let matcher = PiiPatternMatcher::all();
-        let input = b"Phone: 555-123-4567";
-        let result = matcher.redact(input);
+        let hex_start = pos;
+        while pos < chars.len() && pos < hex_start + 4 && chars[pos].is_ascii_hexdigit() {
+            pos += 1;
+        }
+        if pos == hex_start {
+            break;
+        }
+        groups += 1;
+        last_was_colon = false;
 
-        assert!(result.redacted);
-        assert!(result.matched_patterns.contains("phone_us"));
-        assert_eq!(
-            String::from_utf8_lossy(&result.content),
-            "Phone: (XXX) XXX-4567"
-        );
+        if pos < chars.len() && chars[pos] == ':' {
+            continue;
+        }
+        break;
     }
 
-    #[test]
-    fn test_phone_not_redacted_by_default() {
-        let matcher = PiiPatternMatcher::default_patterns();
-        let input = b"Phone: 555-123-4567";
-        let result = matcher.redact(input);
+    if last_was_colon && !saw_compression {
+        return None;
+    }
+    if groups < 2 || groups > 8 {
+        return None;
+    }
+    if groups < 8 && !saw_compression {
+        return None;
+    }
+    if pos == body_start {
+        return None;
+    }
 
-        // Phone is disabled by default
-        assert!(!result.matched_patterns.contains("phone_us"));
-        assert_eq!(
-            String::from_utf8_lossy(&result.content),
-            "Phone: 555-123-4567"
-        );
+    if bracketed {
+        if pos >= chars.len() || chars[pos] != ']' {
+            return None;
+        }
+        pos += 1;
     }
 
-    #[test]
-    fn test_multiple_patterns() {
-        let matcher = PiiPatternMatcher::default_patterns();
-        let input = b"SSN: 123-45-6789, Card: 4111-1111-1111-1111, Email: test@example.com";
-        let result = matcher.redact(input);
+    Some(pos)
+}
 
-        assert!(result.redacted);
-        assert_eq!(result.match_count, 3);
-        assert!(result.matched_patterns.contains("ssn"));
-        assert!(result.matched_patterns.contains("credit_card"));
-        assert!(result.matched_patterns.contains("email"));
-        assert_eq!(
-            String::from_utf8_lossy(&result.content),
+/// Redact all matches of a compiled custom pattern in `input`.
+fn redact_custom(custom: &CompiledCustom, input: &str) -> (String, u32) {
+    let chars: Vec<char> = input.chars().collect();
+    let mut result = String::with_capacity(input.len());
+    let mut i = 0;
+    let mut count = 0;
+
+    while i < chars.len() {
+        match custom.prog.try_match_at(&chars, i) {
+            Some(end) if end > i => {
+                result.push_str(&apply_custom_replacement(&chars[i..end], &custom.replacement));
+                i = end;
+                count += 1;
+            }
+            _ => {
+                result.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    (result, count)
+}
+
+/// Render a matched slice according to its configured replacement style.
+fn apply_custom_replacement(matched: &[char], replacement: &CustomReplacement) -> String {
+    match replacement {
+        CustomReplacement::Fixed(s) => s.clone(),
+        CustomReplacement::PreserveLast(keep) => mask_preserving_last(matched, *keep, 'X'),
+    }
+}
+
+// =============================================================================
+// Redaction Modes
+// =============================================================================
+
+/// Render a matched span per `mode`, falling back to `default_mask` (the
+/// pattern's usual fixed/partial replacement text) when `mode` is `Mask`.
+/// `keep` is how many trailing characters of `matched` survive under
+/// `Partial`.
+fn render_replacement(matched: &[char], mode: RedactionMode, keep: usize, default_mask: &str) -> String {
+    match mode {
+        RedactionMode::Mask => default_mask.to_string(),
+        RedactionMode::Partial => mask_preserving_last(matched, keep, '*'),
+        RedactionMode::Tokenize => tokenize(matched),
+    }
+}
+
+/// Mask every alphanumeric character of `matched` with `mask_char` except
+/// the last `keep` characters, leaving non-alphanumeric separators (dashes,
+/// dots, `@`) untouched - preserves the original length and structure.
+fn mask_preserving_last(matched: &[char], keep: usize, mask_char: char) -> String {
+    let mask_until = matched.len().saturating_sub(keep);
+    matched
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| {
+            if idx < mask_until && c.is_alphanumeric() {
+                mask_char
+            } else {
+                *c
+            }
+        })
+        .collect()
+}
+
+/// Keyed salt mixed into [`tokenize`]'s hash so the derived token isn't
+/// simply the matched value's hash restated in hex.
+const TOKEN_HASH_KEY: u64 = 0x9e3779b97f4a7c15;
+
+/// Derive a deterministic opaque token for `matched` via a keyed FNV-1a
+/// hash, so the same input value always produces the same token (useful for
+/// correlating redacted log entries) without the token exposing the value.
+fn tokenize(matched: &[char]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325 ^ TOKEN_HASH_KEY;
+    for c in matched {
+        let mut buf = [0u8; 4];
+        for &b in c.encode_utf8(&mut buf).as_bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("TOK_{:016x}", hash)
+}
+
+// =============================================================================
+// Streaming Redaction
+// =============================================================================
+
+/// Longest span each fixed-length built-in pattern can match, in chars.
+const CREDIT_CARD_MAX_LEN: usize = 19; // "XXXX-XXXX-XXXX-XXXX"
+const SSN_MAX_LEN: usize = 11; // "XXX-XX-XXXX"
+const PHONE_MAX_LEN: usize = 12; // "XXX-XXX-XXXX"
+/// Longest a literal IPv6 address can render: bracketed, with an embedded
+/// IPv4 tail, e.g. `[ffff:ffff:ffff:ffff:ffff:ffff:255.255.255.255]`.
+const IPV6_MAX_LEN: usize = 47;
+
+/// Longest span any pattern *enabled* on `matcher` can match, including
+/// configured custom patterns with a bounded length. Sizes the carry-over
+/// window `StreamingRedactor` holds back at a chunk boundary so a match
+/// split across two chunks still gets caught - undersizing this window is
+/// what let an IPv6 address straddling a boundary through unredacted, since
+/// IPv6 (up to `IPV6_MAX_LEN`) is longer than a dashed credit card.
+///
+/// Email addresses and custom patterns with an unbounded quantifier (e.g.
+/// `\d+`) have no fixed maximum length and don't contribute to this window.
+/// Such a match that is still incomplete at a chunk boundary is held back
+/// only if it starts inside the window or the part received so far already
+/// matches; otherwise the text before the window is emitted as-is, and only
+/// the remainder can be matched once the next chunk arrives.
+fn carry_window_len(matcher: &PiiPatternMatcher) -> usize {
+    let mut max_len = 1;
+    if matcher.enable_credit_card {
+        max_len = max_len.max(CREDIT_CARD_MAX_LEN);
+    }
+    if matcher.enable_ssn {
+        max_len = max_len.max(SSN_MAX_LEN);
+    }
+    if matcher.enable_phone_us {
+        max_len = max_len.max(PHONE_MAX_LEN);
+    }
+    if matcher.enable_ip {
+        max_len = max_len.max(IPV6_MAX_LEN);
+    }
+    for custom in &matcher.custom {
+        if let Some(len) = custom.prog.max_match_len() {
+            max_len = max_len.max(len);
+        }
+    }
+    max_len
+}
+
+/// One matched-and-replaced region, in char offsets into the buffer it was
+/// found in.
+struct MatchSpan {
+    start: usize,
+    end: usize,
+    name: String,
+    replacement: String,
+}
+
+/// Chunk-boundary-aware redactor for bodies delivered as a sequence of
+/// buffers, as Service Extensions delivers HTTP bodies to `on_http_*_body`.
+///
+/// Unlike [`PiiPatternMatcher::redact`], which needs the whole body in
+/// memory, this holds back only a small tail between calls so a match
+/// straddling two chunks (e.g. a credit card split mid-dash) is still found
+/// once the rest of it arrives.
+pub struct StreamingRedactor {
+    matcher: PiiPatternMatcher,
+    carry: Vec<u8>,
+    match_count: u32,
+    matched_patterns: HashSet<String>,
+    /// Carry-over window size for `matcher`, computed once up front by
+    /// [`carry_window_len`].
+    tail_window: usize,
+}
+
+impl StreamingRedactor {
+    /// Wrap a configured matcher for streaming use.
+    pub fn new(matcher: PiiPatternMatcher) -> Self {
+        let tail_window = carry_window_len(&matcher);
+        Self {
+            matcher,
+            carry: Vec::new(),
+            match_count: 0,
+            matched_patterns: HashSet::new(),
+            tail_window,
+        }
+    }
+
+    /// Total matches redacted so far across every `process_chunk` call.
+    pub fn match_count(&self) -> u32 {
+        self.match_count
+    }
+
+    /// Names of patterns matched so far across every `process_chunk` call.
+    pub fn matched_patterns(&self) -> &HashSet<String> {
+        &self.matched_patterns
+    }
+
+    /// Feed the next chunk of the body and get back the portion of redacted
+    /// output that's safe to emit now. Pass `is_last = true` on the final
+    /// chunk to flush everything, including any held-back tail.
+    pub fn process_chunk(&mut self, bytes: &[u8], is_last: bool) -> Vec<u8> {
+        let mut buffer = std::mem::take(&mut self.carry);
+        buffer.extend_from_slice(bytes);
+
+        // A UTF-8 code point can straddle a chunk boundary; hold back any
+        // incomplete trailing bytes rather than bailing out on the whole
+        // chunk, and fold them in once the rest arrives.
+        let (text, utf8_tail) = if is_last {
+            (String::from_utf8_lossy(&buffer).into_owned(), Vec::new())
+        } else {
+            let safe_len = utf8_safe_prefix_len(&buffer);
+            // Lossy, like the final chunk: the prefix is only guaranteed
+            // valid when the buffer has no invalid bytes further back.
+            let text = String::from_utf8_lossy(&buffer[..safe_len]).into_owned();
+            (text, buffer[safe_len..].to_vec())
+        };
+
+        let chars: Vec<char> = text.chars().collect();
+        let spans = find_all_spans(&chars, &self.matcher);
+
+        // Everything up to this char offset is guaranteed not to be the
+        // prefix of a not-yet-complete match of a fixed-length built-in or
+        // bounded custom pattern, since any such match starting earlier is
+        // short enough to have fully resolved within the buffer already
+        // (see `carry_window_len` for emails and unbounded patterns).
+        let safe_len = if is_last {
+            chars.len()
+        } else {
+            chars.len().saturating_sub(self.tail_window - 1)
+        };
+
+        let mut committed = String::with_capacity(text.len());
+        let mut cursor = 0;
+        let mut defer_from = safe_len;
+
+        for span in &spans {
+            if span.end > safe_len {
+                defer_from = span.start;
+                break;
+            }
+            committed.extend(&chars[cursor..span.start]);
+            committed.push_str(&span.replacement);
+            self.match_count += 1;
+            self.matched_patterns.insert(span.name.clone());
+            cursor = span.end;
+        }
+        committed.extend(&chars[cursor..defer_from]);
+
+        self.carry = chars[defer_from..].iter().collect::<String>().into_bytes();
+        self.carry.extend(utf8_tail);
+
+        committed.into_bytes()
+    }
+}
+
+/// Find the largest prefix of `buffer` that is valid UTF-8, trimming up to 3
+/// trailing bytes that might be an incomplete multi-byte sequence. When no
+/// such prefix exists (the buffer holds invalid bytes further back), returns
+/// the length minus 3, and that prefix is not valid UTF-8.
+fn utf8_safe_prefix_len(buffer: &[u8]) -> usize {
+    let mut len = buffer.len();
+    let floor = len.saturating_sub(3);
+    while len > floor {
+        if std::str::from_utf8(&buffer[..len]).is_ok() {
+            return len;
+        }
+        len -= 1;
+    }
+    if std::str::from_utf8(&buffer[..len]).is_ok() {
+        len
+    } else {
+        floor
+    }
+}
+
+/// Scan `chars` left to right for every enabled pattern, built-in or
+/// custom, returning non-overlapping matches in order.
+fn find_all_spans(chars: &[char], matcher: &PiiPatternMatcher) -> Vec<MatchSpan> {
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(span) = match_any_pattern_at(chars, i, matcher) {
+            i = span.end;
+            spans.push(span);
+        } else {
+            i += 1;
+        }
+    }
+    spans
+}
+
+/// Try every enabled pattern at exactly position `i`, in the same priority
+/// order `apply_patterns` applies its sequential passes, and return the
+/// first that matches there.
+fn match_any_pattern_at(chars: &[char], i: usize, matcher: &PiiPatternMatcher) -> Option<MatchSpan> {
+    if matcher.enable_credit_card {
+        if let Some(span) = match_credit_card_at(chars, i, matcher.credit_card_luhn, matcher.redaction_mode) {
+            return Some(span);
+        }
+    }
+    if matcher.enable_ssn {
+        if let Some(span) = match_ssn_at(chars, i, matcher.redaction_mode) {
+            return Some(span);
+        }
+    }
+    if matcher.enable_email && chars[i] == '@' {
+        // Anchored on the '@', like `redact_email`; the returned span may
+        // start earlier, at the beginning of the local part.
+        if let Some((start, end)) = match_addr_spec_at(chars, i) {
+            let replacement = render_replacement(&chars[start..end], matcher.redaction_mode, 4, "[EMAIL REDACTED]");
+            return Some(MatchSpan {
+                start,
+                end,
+                name: "email".to_string(),
+                replacement,
+            });
+        }
+    }
+    if matcher.enable_phone_us {
+        if let Some(span) = match_phone_at(chars, i, matcher.redaction_mode) {
+            return Some(span);
+        }
+    }
+    if matcher.enable_ip {
+        if let Some(span) = match_ip_at(chars, i, matcher.redaction_mode) {
+            return Some(span);
+        }
+    }
+    for custom in &matcher.custom {
+        if let Some(end) = custom.prog.try_match_at(chars, i) {
+            if end > i {
+                let replacement = apply_custom_replacement(&chars[i..end], &custom.replacement);
+                return Some(MatchSpan {
+                    start: i,
+                    end,
+                    name: custom.name.clone(),
+                    replacement,
+                });
+            }
+        }
+    }
+    None
+}
+
+fn match_credit_card_at(chars: &[char], i: usize, luhn_enabled: bool, mode: RedactionMode) -> Option<MatchSpan> {
+    if i + 19 <= chars.len() {
+        let potential: String = chars[i..i + 19].iter().collect();
+        if is_credit_card_format(&potential) && (!luhn_enabled || passes_luhn(&potential)) {
+            let last_four = &potential[15..19];
+            let default_mask = format!("XXXX-XXXX-XXXX-{}", last_four);
+            return Some(MatchSpan {
+                start: i,
+                end: i + 19,
+                name: "credit_card".to_string(),
+                replacement: render_replacement(&chars[i..i + 19], mode, 4, &default_mask),
+            });
+        }
+    }
+    if i + 16 <= chars.len() && chars[i..i + 16].iter().all(|c| is_digit(*c)) {
+        let before_ok = i == 0 || !is_digit(chars[i - 1]);
+        let after_ok = i + 16 >= chars.len() || !is_digit(chars[i + 16]);
+        let potential: String = chars[i..i + 16].iter().collect();
+        if before_ok && after_ok && (!luhn_enabled || passes_luhn(&potential)) {
+            let last_four: String = chars[i + 12..i + 16].iter().collect();
+            let default_mask = format!("XXXXXXXXXXXX{}", last_four);
+            return Some(MatchSpan {
+                start: i,
+                end: i + 16,
+                name: "credit_card".to_string(),
+                replacement: render_replacement(&chars[i..i + 16], mode, 4, &default_mask),
+            });
+        }
+    }
+    None
+}
+
+fn match_ssn_at(chars: &[char], i: usize, mode: RedactionMode) -> Option<MatchSpan> {
+    if i + 11 > chars.len() {
+        return None;
+    }
+    let potential: String = chars[i..i + 11].iter().collect();
+    if !is_ssn_format(&potential) {
+        return None;
+    }
+    let before_ok = i == 0 || !chars[i - 1].is_alphanumeric();
+    let after_ok = i + 11 >= chars.len() || !chars[i + 11].is_alphanumeric();
+    if before_ok && after_ok {
+        Some(MatchSpan {
+            start: i,
+            end: i + 11,
+            name: "ssn".to_string(),
+            replacement: render_replacement(&chars[i..i + 11], mode, 4, "XXX-XX-XXXX"),
+        })
+    } else {
+        None
+    }
+}
+
+fn match_phone_at(chars: &[char], i: usize, mode: RedactionMode) -> Option<MatchSpan> {
+    if i + 12 > chars.len() {
+        return None;
+    }
+    let potential: String = chars[i..i + 12].iter().collect();
+    if !is_phone_format(&potential) {
+        return None;
+    }
+    let before_ok = i == 0 || !chars[i - 1].is_alphanumeric();
+    let after_ok = i + 12 >= chars.len() || !chars[i + 12].is_alphanumeric();
+    if before_ok && after_ok {
+        let last_four = &potential[8..12];
+        let default_mask = format!("(XXX) XXX-{}", last_four);
+        Some(MatchSpan {
+            start: i,
+            end: i + 12,
+            name: "phone_us".to_string(),
+            replacement: render_replacement(&chars[i..i + 12], mode, 4, &default_mask),
+        })
+    } else {
+        None
+    }
+}
+
+fn match_ip_at(chars: &[char], i: usize, mode: RedactionMode) -> Option<MatchSpan> {
+    let before_ok = i == 0 || !is_word_boundary_char(chars[i - 1]);
+    if !before_ok {
+        return None;
+    }
+    if let Some(end) = match_ipv6(chars, i) {
+        let after_ok = end >= chars.len() || !is_word_boundary_char(chars[end]);
+        if after_ok {
+            return Some(MatchSpan {
+                start: i,
+                end,
+                name: "ip".to_string(),
+                replacement: render_replacement(&chars[i..end], mode, 4, "[IPv6 REDACTED]"),
+            });
+        }
+    }
+    if let Some(end) = match_ipv4(chars, i) {
+        let after_ok = end >= chars.len() || !is_word_boundary_char(chars[end]);
+        if after_ok {
+            return Some(MatchSpan {
+                start: i,
+                end,
+                name: "ip".to_string(),
+                replacement: render_replacement(&chars[i..end], mode, 4, "XXX.XXX.XXX.0"),
+            });
+        }
+    }
+    None
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credit_card_redaction() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Card: 4111-1111-1111-1111";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(result.match_count, 1);
+        assert!(result.matched_patterns.contains("credit_card"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Card: XXXX-XXXX-XXXX-1111"
+        );
+    }
+
+    #[test]
+    fn test_credit_card_no_dash_redaction() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Card: 4111111111111111";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(result.match_count, 1);
+        assert!(result.matched_patterns.contains("credit_card"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Card: XXXXXXXXXXXX1111"
+        );
+    }
+
+    #[test]
+    fn test_luhn_valid_card_is_redacted() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Card: 4111111111111111";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("credit_card"));
+    }
+
+    #[test]
+    fn test_luhn_invalid_digits_are_not_redacted() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Order: 1234567890123456";
+        let result = matcher.redact(input);
+
+        assert!(!result.redacted);
+        assert!(!result.matched_patterns.contains("credit_card"));
+    }
+
+    #[test]
+    fn test_luhn_check_can_be_disabled() {
+        let matcher = PiiPatternMatcher::default_patterns().with_credit_card_luhn(false);
+        let input = b"Order: 1234567890123456";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("credit_card"));
+    }
+
+    #[test]
+    fn test_ssn_redaction() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"SSN: 123-45-6789";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(result.match_count, 1);
+        assert!(result.matched_patterns.contains("ssn"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "SSN: XXX-XX-XXXX"
+        );
+    }
+
+    #[test]
+    fn test_email_redaction() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Email: john.doe@example.com";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(result.match_count, 1);
+        assert!(result.matched_patterns.contains("email"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Email: [EMAIL REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_email_quoted_local_part() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = "Email: \"john doe\"@example.com".as_bytes();
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Email: [EMAIL REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_email_display_name_form() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"From: John Doe <j.doe+tag@sub.example.com>";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "From: John Doe <[EMAIL REDACTED]>"
+        );
+    }
+
+    #[test]
+    fn test_email_ip_literal_domain() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Email: user@[192.168.0.1]";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Email: [EMAIL REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_email_rejects_trailing_and_leading_dots() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Not emails: foo.@bar.com and a@.com";
+        let result = matcher.redact(input);
+
+        assert!(!result.redacted);
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Not emails: foo.@bar.com and a@.com"
+        );
+    }
+
+    #[test]
+    fn test_email_multibyte_local_part() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = "Email: jos\u{e9}@example.com".as_bytes();
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Email: [EMAIL REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_phone_redaction_when_enabled() {
+        let matcher = PiiPatternMatcher::all();
+        let input = b"Phone: 555-123-4567";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("phone_us"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Phone: (XXX) XXX-4567"
+        );
+    }
+
+    #[test]
+    fn test_phone_not_redacted_by_default() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Phone: 555-123-4567";
+        let result = matcher.redact(input);
+
+        // Phone is disabled by default
+        assert!(!result.matched_patterns.contains("phone_us"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Phone: 555-123-4567"
+        );
+    }
+
+    #[test]
+    fn test_ipv4_redaction() {
+        let matcher = PiiPatternMatcher::all();
+        let input = b"Client IP: 192.168.1.42 connected";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("ip"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Client IP: XXX.XXX.XXX.0 connected"
+        );
+    }
+
+    #[test]
+    fn test_ipv4_rejects_out_of_range_octet() {
+        let matcher = PiiPatternMatcher::all();
+        let input = b"Not an IP: 999.256.1.1 or 10.0.0.01";
+        let result = matcher.redact(input);
+
+        assert!(!result.matched_patterns.contains("ip"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Not an IP: 999.256.1.1 or 10.0.0.01"
+        );
+    }
+
+    #[test]
+    fn test_ipv6_redaction() {
+        let matcher = PiiPatternMatcher::all();
+        let input = b"Host: 2001:db8::1 replied";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("ip"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Host: [IPv6 REDACTED] replied"
+        );
+    }
+
+    #[test]
+    fn test_ipv6_bracketed_with_embedded_ipv4() {
+        let matcher = PiiPatternMatcher::all();
+        let input = b"Host: [::ffff:192.168.0.1]:8080";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Host: [IPv6 REDACTED]:8080"
+        );
+    }
+
+    #[test]
+    fn test_ip_not_redacted_by_default() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"Client IP: 192.168.1.42";
+        let result = matcher.redact(input);
+
+        assert!(!result.matched_patterns.contains("ip"));
+    }
+
+    #[test]
+    fn test_multiple_patterns() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = b"SSN: 123-45-6789, Card: 4111-1111-1111-1111, Email: test@example.com";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(result.match_count, 3);
+        assert!(result.matched_patterns.contains("ssn"));
+        assert!(result.matched_patterns.contains("credit_card"));
+        assert!(result.matched_patterns.contains("email"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
             "SSN: XXX-XX-XXXX, Card: XXXX-XXXX-XXXX-1111, Email: [EMAIL REDACTED]"
         );
     }
@@ -556,6 +1639,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_custom_pattern_fixed_replacement() {
+        let matcher = PiiPatternMatcher::new(false, false, false, false, false).with_custom_patterns(vec![
+            PiiPattern::Custom {
+                name: "passport".to_string(),
+                pattern: r"[A-Z]{2}\d{7}".to_string(),
+                replacement: CustomReplacement::Fixed("[PASSPORT REDACTED]".to_string()),
+            },
+        ]);
+        let input = b"Passport: AB1234567 on file";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(result.match_count, 1);
+        assert!(result.matched_patterns.contains("passport"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Passport: [PASSPORT REDACTED] on file"
+        );
+    }
+
+    #[test]
+    fn test_custom_pattern_preserve_last() {
+        let matcher = PiiPatternMatcher::new(false, false, false, false, false).with_custom_patterns(vec![
+            PiiPattern::Custom {
+                name: "iban".to_string(),
+                pattern: r"[A-Z]{2}\d{2}[A-Z0-9]{4,30}".to_string(),
+                replacement: CustomReplacement::PreserveLast(4),
+            },
+        ]);
+        let input = b"IBAN: GB29NWBK60161331926819";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("iban"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "IBAN: XXXXXXXXXXXXXXXXXX6819"
+        );
+    }
+
     #[test]
     fn test_preserves_surrounding_text() {
         let matcher = PiiPatternMatcher::default_patterns();
@@ -567,4 +1691,180 @@ mod tests {
             "Before XXX-XX-XXXX After"
         );
     }
+
+    #[test]
+    fn test_decode_finds_ssn_inside_base64_body() {
+        let matcher = PiiPatternMatcher::default_patterns().with_decode_config(DecodeConfig {
+            enabled: true,
+            declared_charset: None,
+        });
+        let input = crate::encoding::encode_base64(b"SSN on file: 123-45-6789");
+        let result = matcher.redact(&input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("ssn"));
+        assert_eq!(result.decoded_region, Some((0, input.len())));
+
+        let decoded_back = crate::encoding::decode_base64(&result.content).unwrap();
+        assert_eq!(
+            String::from_utf8(decoded_back).unwrap(),
+            "SSN on file: XXX-XX-XXXX"
+        );
+    }
+
+    #[test]
+    fn test_decode_finds_card_inside_quoted_printable_body() {
+        let matcher = PiiPatternMatcher::default_patterns().with_decode_config(DecodeConfig {
+            enabled: true,
+            declared_charset: None,
+        });
+        let input = b"Card: 4111-1111-1111-1111=\r\nMore text";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert!(result.matched_patterns.contains("credit_card"));
+        assert_eq!(result.decoded_region, Some((0, input.len())));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Card: XXXX-XXXX-XXXX-1111More text"
+        );
+    }
+
+    #[test]
+    fn test_decode_disabled_leaves_encoded_body_untouched() {
+        let matcher = PiiPatternMatcher::default_patterns();
+        let input = crate::encoding::encode_base64(b"SSN on file: 123-45-6789");
+        let result = matcher.redact(&input);
+
+        assert!(!result.redacted);
+        assert_eq!(result.decoded_region, None);
+        assert_eq!(result.content, input);
+    }
+
+    #[test]
+    fn test_streaming_redacts_within_a_single_chunk() {
+        let mut streaming = StreamingRedactor::new(PiiPatternMatcher::default_patterns());
+        let mut out = streaming.process_chunk(b"SSN: 123-45-6789", false);
+        out.extend(streaming.process_chunk(b"", true));
+
+        assert_eq!(String::from_utf8(out).unwrap(), "SSN: XXX-XX-XXXX");
+        assert_eq!(streaming.match_count(), 1);
+        assert!(streaming.matched_patterns().contains("ssn"));
+    }
+
+    #[test]
+    fn test_streaming_catches_card_split_across_chunks() {
+        let mut streaming = StreamingRedactor::new(PiiPatternMatcher::default_patterns());
+        let mut out = streaming.process_chunk(b"Card: 4111-1111-", false);
+        out.extend(streaming.process_chunk(b"1111-1111 on file", true));
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Card: XXXX-XXXX-XXXX-1111 on file"
+        );
+        assert_eq!(streaming.match_count(), 1);
+    }
+
+    #[test]
+    fn test_streaming_never_emits_a_truncated_match() {
+        // Feed the card one byte at a time; no intermediate chunk's output
+        // may contain a bare digit run that's actually a prefix of the card.
+        let mut streaming = StreamingRedactor::new(PiiPatternMatcher::default_patterns());
+        let card = b"4111-1111-1111-1111";
+        let mut out = Vec::new();
+        for (idx, &b) in card.iter().enumerate() {
+            let is_last = idx == card.len() - 1;
+            out.extend(streaming.process_chunk(&[b], is_last));
+        }
+
+        assert_eq!(String::from_utf8(out).unwrap(), "XXXX-XXXX-XXXX-1111");
+        assert_eq!(streaming.match_count(), 1);
+    }
+
+    #[test]
+    fn test_streaming_passes_through_plain_text_chunks() {
+        let mut streaming = StreamingRedactor::new(PiiPatternMatcher::default_patterns());
+        let mut out = streaming.process_chunk(b"Hello, ", false);
+        out.extend(streaming.process_chunk(b"World!", true));
+
+        assert_eq!(String::from_utf8(out).unwrap(), "Hello, World!");
+        assert_eq!(streaming.match_count(), 0);
+    }
+
+    #[test]
+    fn test_partial_mode_preserves_card_structure() {
+        let matcher = PiiPatternMatcher::default_patterns().with_redaction_mode(RedactionMode::Partial);
+        let input = b"Card: 4111-1111-1111-1111";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(result.match_count, 1);
+        assert!(result.matched_patterns.contains("credit_card"));
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "Card: ****-****-****-1111"
+        );
+    }
+
+    #[test]
+    fn test_partial_mode_preserves_ssn_structure() {
+        let matcher = PiiPatternMatcher::default_patterns().with_redaction_mode(RedactionMode::Partial);
+        let input = b"SSN: 123-45-6789";
+        let result = matcher.redact(input);
+
+        assert!(result.redacted);
+        assert_eq!(
+            String::from_utf8_lossy(&result.content),
+            "SSN: ***-**-6789"
+        );
+    }
+
+    #[test]
+    fn test_tokenize_mode_is_deterministic_and_consistent_within_a_response() {
+        let matcher = PiiPatternMatcher::default_patterns().with_redaction_mode(RedactionMode::Tokenize);
+
+        let first = matcher.redact(b"SSN: 123-45-6789");
+        let second = matcher.redact(b"SSN: 123-45-6789");
+        let different = matcher.redact(b"SSN: 999-99-9999");
+
+        let first_token = String::from_utf8(first.content).unwrap();
+        let second_token = String::from_utf8(second.content).unwrap();
+        let different_token = String::from_utf8(different.content).unwrap();
+
+        assert!(!first_token.contains("123-45-6789"));
+        assert!(first_token.starts_with("SSN: TOK_"));
+        // The same matched value always tokenizes to the same opaque value...
+        assert_eq!(first_token, second_token);
+        // ...while a different value tokenizes differently.
+        assert_ne!(first_token, different_token);
+    }
+
+    #[test]
+    fn test_streaming_handles_multibyte_char_split_across_chunks() {
+        let bytes = "caf\u{e9}".as_bytes();
+        let mut streaming = StreamingRedactor::new(PiiPatternMatcher::default_patterns());
+        // Split right in the middle of the 2-byte UTF-8 encoding of 'é'.
+        let mut out = streaming.process_chunk(&bytes[..bytes.len() - 1], false);
+        out.extend(streaming.process_chunk(&bytes[bytes.len() - 1..], true));
+
+        assert_eq!(String::from_utf8(out).unwrap(), "caf\u{e9}");
+    }
+
+    #[test]
+    fn test_streaming_catches_ipv6_split_across_chunks() {
+        // The IPv6 pattern is longer than the 19-char dashed credit card;
+        // a fixed 19-byte carry window would commit the first chunk's tail
+        // before the address completes, leaking it unredacted.
+        let matcher = PiiPatternMatcher::new(false, false, false, false, true);
+        let mut streaming = StreamingRedactor::new(matcher);
+        let mut out = streaming.process_chunk(b"Host: 2001:0db8:0000:0000:0000:ff00:", false);
+        out.extend(streaming.process_chunk(b"0042:8329 connected", true));
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Host: [IPv6 REDACTED] connected"
+        );
+        assert_eq!(streaming.match_count(), 1);
+        assert!(streaming.matched_patterns().contains("ip"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,177 @@
+//! Response security-header hardening policy.
+//!
+//! Both plugins carry an identical copy of this module and apply it from
+//! `on_http_response_headers`, so a deployment gets the same baseline
+//! hardening (`X-Content-Type-Options`, `X-Frame-Options`,
+//! `Content-Security-Policy`, `Permissions-Policy`,
+//! `Strict-Transport-Security`) regardless of which filter sits in the
+//! response path. Upgraded connections (e.g. websockets) are detected via
+//! [`is_upgraded_connection`] and must be skipped by the caller, since
+//! adding framing headers to an upgraded stream breaks reverse-proxied
+//! websockets.
+//!
+//! TECH DEBT: this file is a byte-for-byte copy of the other plugin's
+//! `security_headers.rs`, not a deliberate synced design - there's no
+//! shared crate for the two plugins to depend on. Any change to header
+//! policy here (new header, bug fix, new test) must be applied to both
+//! copies by hand; it has already drifted once. If a third copy is ever
+//! needed, or this one changes again, pull this module into a shared
+//! crate instead of copying it again.
+
+use serde::Deserialize;
+
+/// Per-header enable flag plus an optional override value. Leaving a
+/// `*_value` unset falls back to a conservative built-in default.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityHeaderPolicy {
+    #[serde(default = "default_true")]
+    pub x_content_type_options: bool,
+
+    #[serde(default = "default_true")]
+    pub x_frame_options: bool,
+    #[serde(default)]
+    pub x_frame_options_value: Option<String>,
+
+    #[serde(default)]
+    pub content_security_policy: bool,
+    #[serde(default)]
+    pub content_security_policy_value: Option<String>,
+
+    #[serde(default)]
+    pub permissions_policy: bool,
+    #[serde(default)]
+    pub permissions_policy_value: Option<String>,
+
+    #[serde(default)]
+    pub strict_transport_security: bool,
+    #[serde(default)]
+    pub strict_transport_security_value: Option<String>,
+}
+
+impl Default for SecurityHeaderPolicy {
+    fn default() -> Self {
+        Self {
+            x_content_type_options: true,
+            x_frame_options: true,
+            x_frame_options_value: None,
+            content_security_policy: false,
+            content_security_policy_value: None,
+            permissions_policy: false,
+            permissions_policy_value: None,
+            strict_transport_security: false,
+            strict_transport_security_value: None,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Whether `connection`/`upgrade` indicate the request or response
+/// negotiated a protocol upgrade (a websocket being the common case).
+/// Callers should check both the request-side and response-side headers,
+/// since either side may carry the negotiation.
+pub fn is_upgraded_connection(connection: Option<&str>, upgrade: Option<&str>) -> bool {
+    let connection_says_upgrade = connection
+        .map(|value| {
+            value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+        })
+        .unwrap_or(false);
+    let upgrade_present = upgrade.map(|value| !value.trim().is_empty()).unwrap_or(false);
+    connection_says_upgrade || upgrade_present
+}
+
+/// The `(name, value)` headers to add for `policy`, in a fixed, readable
+/// order. Does not itself consult [`is_upgraded_connection`] - callers must
+/// skip calling this entirely for upgraded connections.
+pub fn headers_to_add(policy: &SecurityHeaderPolicy) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::new();
+
+    if policy.x_content_type_options {
+        headers.push(("X-Content-Type-Options", "nosniff".to_string()));
+    }
+    if policy.x_frame_options {
+        let value = policy.x_frame_options_value.clone().unwrap_or_else(|| "DENY".to_string());
+        headers.push(("X-Frame-Options", value));
+    }
+    if policy.content_security_policy {
+        let value = policy
+            .content_security_policy_value
+            .clone()
+            .unwrap_or_else(|| "default-src 'self'".to_string());
+        headers.push(("Content-Security-Policy", value));
+    }
+    if policy.permissions_policy {
+        let value = policy
+            .permissions_policy_value
+            .clone()
+            .unwrap_or_else(|| "geolocation=(), camera=(), microphone=()".to_string());
+        headers.push(("Permissions-Policy", value));
+    }
+    if policy.strict_transport_security {
+        let value = policy
+            .strict_transport_security_value
+            .clone()
+            .unwrap_or_else(|| "max-age=63072000; includeSubDomains".to_string());
+        headers.push(("Strict-Transport-Security", value));
+    }
+
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy_only_adds_content_type_and_frame_options() {
+        let policy = SecurityHeaderPolicy::default();
+        let headers = headers_to_add(&policy);
+
+        assert_eq!(
+            headers,
+            vec![
+                ("X-Content-Type-Options", "nosniff".to_string()),
+                ("X-Frame-Options", "DENY".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_override_value_replaces_the_default() {
+        let policy = SecurityHeaderPolicy {
+            x_frame_options_value: Some("SAMEORIGIN".to_string()),
+            ..SecurityHeaderPolicy::default()
+        };
+        let headers = headers_to_add(&policy);
+
+        assert!(headers.contains(&("X-Frame-Options", "SAMEORIGIN".to_string())));
+    }
+
+    #[test]
+    fn test_disabled_header_is_not_added() {
+        let policy = SecurityHeaderPolicy {
+            x_content_type_options: false,
+            x_frame_options: false,
+            ..SecurityHeaderPolicy::default()
+        };
+        assert!(headers_to_add(&policy).is_empty());
+    }
+
+    #[test]
+    fn test_connection_upgrade_token_is_detected_case_insensitively() {
+        assert!(is_upgraded_connection(Some("Upgrade"), Some("websocket")));
+        assert!(is_upgraded_connection(Some("keep-alive, Upgrade"), None));
+        assert!(!is_upgraded_connection(Some("keep-alive"), None));
+        assert!(!is_upgraded_connection(None, None));
+    }
+
+    #[test]
+    fn test_upgrade_header_alone_counts_as_upgraded() {
+        assert!(is_upgraded_connection(None, Some("websocket")));
+        assert!(!is_upgraded_connection(None, Some("")));
+    }
+}
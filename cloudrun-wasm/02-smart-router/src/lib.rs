@@ -7,11 +7,16 @@
 //! - Location: Request Path
 //! - Callback: `on_http_request_headers`
 
+mod security_headers;
+
 use log::{info, warn};
 use proxy_wasm::traits::{Context, HttpContext, RootContext};
-use proxy_wasm::types::{Action, ContextType};
+use proxy_wasm::types::{Action, ContextType, Status};
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use security_headers::SecurityHeaderPolicy;
 
 // =============================================================================
 // Configuration
@@ -26,6 +31,28 @@ struct RoutingRule {
     target: String,
     #[serde(default)]
     add_headers: HashMap<String, String>,
+
+    /// Percentage (0-100) of traffic matching `conditions` that should
+    /// actually receive `target`. `None` means always-on, the pre-canary
+    /// behavior.
+    #[serde(default)]
+    weight: Option<u8>,
+    /// Cookie/header identifying the caller, used to deterministically
+    /// bucket it so the same caller never flips between targets on retry.
+    #[serde(default)]
+    sticky_key: Option<StickyKey>,
+
+    /// Name of a `services` entry to consult before this rule is allowed to
+    /// fire. `None` means the rule decides purely from `conditions`/`weight`.
+    #[serde(default)]
+    decision_service: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct StickyKey {
+    #[serde(rename = "type")]
+    key_type: String,
+    key: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -51,6 +78,53 @@ struct PluginConfig {
 
     #[serde(default)]
     rules: Vec<RoutingRule>,
+
+    /// Named external gRPC decision services that rules can reference via
+    /// `decision_service` (e.g. a feature-flag or authorization backend).
+    #[serde(default)]
+    services: HashMap<String, GrpcServiceConfig>,
+
+    /// Response security-header hardening, applied on every response except
+    /// upgraded (e.g. websocket) connections.
+    #[serde(default)]
+    security_headers: SecurityHeaderPolicy,
+}
+
+/// An external gRPC service consulted before a matching rule is allowed to
+/// fire.
+#[derive(Debug, Clone, Deserialize)]
+struct GrpcServiceConfig {
+    /// Envoy upstream cluster name hosting the service.
+    upstream: String,
+    /// Fully-qualified gRPC service name, e.g. `"flagservice.FlagService"`.
+    service: String,
+    /// RPC method to invoke, e.g. `"Evaluate"`.
+    method: String,
+    #[serde(default = "default_grpc_timeout_ms")]
+    timeout_ms: u64,
+    /// What to do when the call errors out or times out.
+    #[serde(default)]
+    failure_mode: FailureMode,
+}
+
+fn default_grpc_timeout_ms() -> u64 {
+    200
+}
+
+/// Behavior when a rule's decision-service call fails (error or timeout).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FailureMode {
+    /// Reject the request locally with a 403.
+    Deny,
+    /// Fall through as if the service had granted the rule.
+    Allow,
+}
+
+impl Default for FailureMode {
+    fn default() -> Self {
+        FailureMode::Allow
+    }
 }
 
 fn default_log_level() -> String {
@@ -68,6 +142,8 @@ impl Default for PluginConfig {
             default_target: default_target(),
             debug: false,
             rules: vec![],
+            services: HashMap::new(),
+            security_headers: SecurityHeaderPolicy::default(),
         }
     }
 }
@@ -122,10 +198,51 @@ impl RootContext for SmartRouterRoot {
 // HTTP Context
 // =============================================================================
 
+/// State of a rule's outstanding decision-service call, tracked so a
+/// response can be matched back to the request that's paused waiting for
+/// it and the dispatcher knows whether it's still owed a call.
+enum OperationState {
+    /// No call has been dispatched yet for the rule at `cursor`.
+    Pending,
+    /// A call is in flight; `on_grpc_call_response` must see this `call_id`.
+    Waiting(u32),
+}
+
+/// Routing state captured across the `Action::Pause` boundary while a rule's
+/// decision-service call is outstanding. The rule list is evaluated in
+/// priority order starting at `cursor`; only one decision call is ever in
+/// flight at a time.
+struct RouteDispatch {
+    rules: Vec<RoutingRule>,
+    cursor: usize,
+    headers: HashMap<String, String>,
+    cookies: HashMap<String, String>,
+    current_path: String,
+    operation: OperationState,
+    /// The request's incoming `baggage` header, if any, to merge the
+    /// routing decision into once a rule is finalized.
+    baggage: Option<String>,
+    /// A freshly generated sticky value staged by the rule currently at
+    /// `cursor`, as `(rule_name, cookie_name, value)`. Only committed to
+    /// `pending_sticky_cookie` by `finalize_routing` if `rule_name` matches
+    /// the rule that actually wins routing - a rule that passes its weight
+    /// gate but is then denied by `decision_service`, or superseded by a
+    /// later rule, must not pin the client to a bucket it was never routed
+    /// to.
+    candidate_sticky_cookie: Option<(String, String, String)>,
+}
+
 // HTTP context for processing individual requests
 struct SmartRouterHttp {
     context_id: u32,
     config: PluginConfig,
+    /// A sticky cookie to set on the response because this request generated
+    /// a fresh bucket assignment (name, value), set during request processing
+    /// and emitted in `on_http_response_headers`.
+    pending_sticky_cookie: Option<(String, String)>,
+    /// Routing state while a decision-service call is in flight; `None` once
+    /// routing has been finalized.
+    dispatch: Option<RouteDispatch>,
 }
 
 impl SmartRouterHttp {
@@ -133,73 +250,362 @@ impl SmartRouterHttp {
         Self {
             context_id,
             config,
+            pending_sticky_cookie: None,
+            dispatch: None,
         }
     }
-}
 
-impl Context for SmartRouterHttp {}
+    /// Inject the configured security-header set, unless this is an
+    /// upgraded (e.g. websocket) connection, where adding framing headers
+    /// would break the proxied stream.
+    fn apply_security_headers(&self) {
+        let connection = self
+            .get_http_response_header("connection")
+            .or_else(|| self.get_http_request_header("connection"));
+        let upgrade = self
+            .get_http_response_header("upgrade")
+            .or_else(|| self.get_http_request_header("upgrade"));
 
-impl HttpContext for SmartRouterHttp {
-    fn on_http_request_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
-        info!("[{}] Processing request", self.context_id);
+        if security_headers::is_upgraded_connection(connection.as_deref(), upgrade.as_deref()) {
+            info!("[{}] Skipping security headers for upgraded connection", self.context_id);
+            return;
+        }
 
-        // Get current path
-        let current_path = self.get_http_request_header(":path").unwrap_or_else(|| "/".to_string());
+        for (name, value) in security_headers::headers_to_add(&self.config.security_headers) {
+            self.add_http_response_header(name, &value);
+        }
+    }
 
-        // Collect all headers for evaluation
-        let mut headers = HashMap::new();
-        let all_headers = self.get_http_request_headers();
-        for (name, value) in all_headers {
-            headers.insert(name.to_lowercase(), value);
+    /// Resolve the value used to bucket this request for `rule`: the sticky
+    /// key's current header/cookie value, or a freshly generated id when
+    /// it's absent. Returns the value and whether it was freshly generated.
+    fn resolve_sticky_value(
+        &self,
+        rule: &RoutingRule,
+        headers: &HashMap<String, String>,
+        cookies: &HashMap<String, String>,
+    ) -> (String, bool) {
+        let existing = rule.sticky_key.as_ref().and_then(|sticky_key| {
+            match sticky_key.key_type.as_str() {
+                "header" => headers.get(&sticky_key.key.to_lowercase()).cloned(),
+                "cookie" => cookies.get(&sticky_key.key.to_lowercase()).cloned(),
+                _ => {
+                    warn!("Unknown sticky_key type: {}", sticky_key.key_type);
+                    None
+                }
+            }
+        });
+
+        match existing {
+            Some(value) => (value, false),
+            None => (generate_sticky_id(self.context_id, self.get_current_time()), true),
         }
+    }
 
-        // Parse cookies
-        let cookies = parse_cookies(&headers.get("cookie").cloned().unwrap_or_default());
+    /// Decide whether `rule`'s canary weight fires for this request, via a
+    /// consistent-hash bucket of its sticky value. Returns whether it fired,
+    /// plus a freshly generated sticky value to stage as a candidate cookie
+    /// if `rule` turns out to be the one `finalize_routing` confirms.
+    fn matches_canary_weight(
+        &self,
+        rule: &RoutingRule,
+        weight: u8,
+        headers: &HashMap<String, String>,
+        cookies: &HashMap<String, String>,
+    ) -> (bool, Option<(String, String)>) {
+        let (value, generated) = self.resolve_sticky_value(rule, headers, cookies);
+        let bucket = (fnv1a_hash64(value.as_bytes()) % 100) as u8;
 
-        // Sort rules by priority (lower number = higher priority)
-        let mut sorted_rules = self.config.rules.clone();
-        sorted_rules.sort_by_key(|rule| rule.priority);
+        let candidate = if generated {
+            rule.sticky_key.as_ref().map(|sticky_key| (sticky_key.key.clone(), value))
+        } else {
+            None
+        };
+
+        (bucket < weight, candidate)
+    }
 
-        // Find matching rule
-        let mut matched_rule = None;
-        for rule in &sorted_rules {
-            if evaluate_conditions(&rule.conditions, &headers, &cookies) {
-                info!("[{}] Rule '{}' matched", self.context_id, rule.name);
-                matched_rule = Some(rule);
-                break;
+    /// Drive the rule scan starting at `self.dispatch`'s current cursor,
+    /// advancing past rules that don't match and pausing on the first one
+    /// whose `decision_service` requires an outstanding gRPC call.
+    fn drive_routing(&mut self) -> Action {
+        loop {
+            let rule = {
+                let dispatch = self
+                    .dispatch
+                    .as_ref()
+                    .expect("drive_routing requires a dispatch in progress");
+                if dispatch.cursor >= dispatch.rules.len() {
+                    self.finalize_routing(None);
+                    return Action::Continue;
+                }
+                dispatch.rules[dispatch.cursor].clone()
+            };
+
+            let dispatch = self.dispatch.as_ref().unwrap();
+            let headers = dispatch.headers.clone();
+            let cookies = dispatch.cookies.clone();
+
+            if !evaluate_conditions(&rule.conditions, &headers, &cookies) {
+                self.dispatch.as_mut().unwrap().cursor += 1;
+                continue;
+            }
+
+            if let Some(weight) = rule.weight {
+                let (matched, candidate) = self.matches_canary_weight(&rule, weight, &headers, &cookies);
+                if !matched {
+                    self.dispatch.as_mut().unwrap().cursor += 1;
+                    continue;
+                }
+                self.dispatch.as_mut().unwrap().candidate_sticky_cookie =
+                    candidate.map(|(name, value)| (rule.name.clone(), name, value));
+            }
+
+            match &rule.decision_service {
+                None => {
+                    self.finalize_routing(Some(rule));
+                    return Action::Continue;
+                }
+                Some(service_name) => return self.dispatch_decision_call(service_name, &rule),
+            }
+        }
+    }
+
+    /// Issue the decision-service call for `rule` and pause the request, or
+    /// apply the service's `failure_mode` immediately if dispatch itself
+    /// fails (e.g. the upstream cluster is unknown).
+    fn dispatch_decision_call(&mut self, service_name: &str, rule: &RoutingRule) -> Action {
+        let service = match self.config.services.get(service_name) {
+            Some(service) => service.clone(),
+            None => {
+                warn!(
+                    "[{}] Rule '{}' references unknown service '{}'; skipping",
+                    self.context_id, rule.name, service_name
+                );
+                self.dispatch.as_mut().unwrap().cursor += 1;
+                return self.drive_routing();
+            }
+        };
+
+        let result = self.dispatch_grpc_call(
+            &service.upstream,
+            &service.service,
+            &service.method,
+            vec![],
+            None,
+            Duration::from_millis(service.timeout_ms),
+        );
+
+        match result {
+            Ok(call_id) => {
+                self.dispatch.as_mut().unwrap().operation = OperationState::Waiting(call_id);
+                Action::Pause
+            }
+            Err(status) => {
+                warn!(
+                    "[{}] Failed to dispatch decision call for rule '{}': {:?}",
+                    self.context_id, rule.name, status
+                );
+                self.resolve_decision_failure(&service, rule.clone())
+            }
+        }
+    }
+
+    /// Apply a decision-service call's `failure_mode` once it's known the
+    /// call errored, timed out, or could not be dispatched at all.
+    fn resolve_decision_failure(&mut self, service: &GrpcServiceConfig, rule: RoutingRule) -> Action {
+        match service.failure_mode {
+            FailureMode::Allow => {
+                info!(
+                    "[{}] Decision service unavailable, allowing rule '{}' (failure_mode=allow)",
+                    self.context_id, rule.name
+                );
+                self.finalize_routing(Some(rule));
+                Action::Continue
+            }
+            FailureMode::Deny => {
+                warn!(
+                    "[{}] Decision service unavailable, denying request (failure_mode=deny)",
+                    self.context_id
+                );
+                self.dispatch = None;
+                self.send_http_response(403, vec![("content-type", "text/plain")], Some(b"Forbidden by routing policy"));
+                Action::Pause
+            }
+        }
+    }
+
+    /// Apply the routing outcome (matched rule, or the default target) to
+    /// the request and clear `self.dispatch`.
+    fn finalize_routing(&mut self, matched_rule: Option<RoutingRule>) {
+        let (current_path, baggage, candidate_sticky_cookie) = self
+            .dispatch
+            .as_ref()
+            .map(|dispatch| {
+                (
+                    dispatch.current_path.clone(),
+                    dispatch.baggage.clone(),
+                    dispatch.candidate_sticky_cookie.clone(),
+                )
+            })
+            .unwrap_or_default();
+        self.dispatch = None;
+
+        if let Some((candidate_rule, name, value)) = candidate_sticky_cookie {
+            if matched_rule.as_ref().map_or(false, |rule| rule.name == candidate_rule) {
+                self.pending_sticky_cookie = Some((name, value));
             }
         }
 
-        // Use matched rule or default
         let (target, reason, headers_to_add) = if let Some(rule) = matched_rule {
-            (rule.target.clone(), rule.name.clone(), rule.add_headers.clone())
+            info!("[{}] Rule '{}' matched", self.context_id, rule.name);
+            (rule.target, rule.name, rule.add_headers)
         } else {
             (self.config.default_target.clone(), "default".to_string(), HashMap::new())
         };
 
         info!("[{}] Routing decision: target={} reason={}", self.context_id, target, reason);
 
-        // Add routing headers from rule or defaults
         self.add_http_request_header("X-Routed-By", "smart-router");
         self.add_http_request_header("X-Route-Reason", &reason);
-        
-        // Add any additional headers from the rule
+
         for (key, value) in &headers_to_add {
             self.add_http_request_header(key, value);
         }
 
-        // If target v2 and not already /v2, rewrite path
         if target == "v2" && !current_path.starts_with("/v2") {
             let new_path = format!("/v2{}", current_path);
             self.set_http_request_header(":path", Some(&new_path));
             info!("[{}] Rewrote path from {} to {}", self.context_id, current_path, new_path);
         }
 
-        Action::Continue
+        let merged_baggage = build_baggage(baggage.as_deref(), &target, &reason);
+        self.set_http_request_header("baggage", Some(&merged_baggage));
+    }
+}
+
+impl Context for SmartRouterHttp {
+    fn on_grpc_call_response(&mut self, call_id: u32, status_code: u32, response_size: usize) {
+        let (rule, service) = match self.dispatch.as_ref() {
+            Some(dispatch) => match dispatch.operation {
+                OperationState::Waiting(waiting_id) if waiting_id == call_id => {
+                    let rule = dispatch.rules[dispatch.cursor].clone();
+                    let service_name = rule
+                        .decision_service
+                        .clone()
+                        .expect("dispatched rule always names a decision_service");
+                    let service = self
+                        .config
+                        .services
+                        .get(&service_name)
+                        .cloned()
+                        .expect("service existed when the call was dispatched");
+                    (rule, service)
+                }
+                _ => {
+                    warn!("[{}] Got gRPC response for unexpected call {}", self.context_id, call_id);
+                    return;
+                }
+            },
+            None => {
+                warn!("[{}] Got gRPC response for call {} with no pending dispatch", self.context_id, call_id);
+                return;
+            }
+        };
+
+        if status_code != 0 {
+            warn!(
+                "[{}] Decision call for rule '{}' failed with status {}",
+                self.context_id, rule.name, status_code
+            );
+        }
+        let response_first_byte = if status_code == 0 {
+            self.get_grpc_call_response_body(0, response_size)
+                .and_then(|body| body.first().copied())
+        } else {
+            None
+        };
+
+        match decide_grpc_outcome(status_code, service.failure_mode, response_first_byte) {
+            GrpcDecisionOutcome::Allow => {
+                self.finalize_routing(Some(rule));
+                self.resume_http_request();
+            }
+            GrpcDecisionOutcome::DenyRequest => {
+                self.dispatch = None;
+                self.send_http_response(403, vec![("content-type", "text/plain")], Some(b"Forbidden by routing policy"));
+            }
+            GrpcDecisionOutcome::TryNextRule => {
+                // Decision service denied this rule; advance to the next candidate.
+                let dispatch = self.dispatch.as_mut().unwrap();
+                dispatch.cursor += 1;
+                dispatch.operation = OperationState::Pending;
+                let action = self.drive_routing();
+                if should_resume_after_drive(action) {
+                    self.resume_http_request();
+                }
+            }
+        }
+    }
+}
+
+impl HttpContext for SmartRouterHttp {
+    fn on_http_request_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
+        info!("[{}] Processing request", self.context_id);
+
+        // Get current path
+        let current_path = self.get_http_request_header(":path").unwrap_or_else(|| "/".to_string());
+
+        // Collect all headers for evaluation
+        let mut headers = HashMap::new();
+        let all_headers = self.get_http_request_headers();
+        for (name, value) in all_headers {
+            headers.insert(name.to_lowercase(), value);
+        }
+
+        // Parse cookies
+        let cookies = parse_cookies(&headers.get("cookie").cloned().unwrap_or_default());
+
+        // Propagate (or start) the W3C trace context for this hop.
+        let incoming_traceparent = headers.get("traceparent").and_then(|value| parse_traceparent(value));
+        let trace_context = incoming_traceparent
+            .clone()
+            .unwrap_or_else(|| generate_trace_context(self.context_id, self.get_current_time()));
+        self.set_http_request_header("traceparent", Some(&trace_context.to_header()));
+        if incoming_traceparent.is_none() {
+            // A regenerated trace starts fresh; any inherited tracestate no
+            // longer refers to this trace-id.
+            self.set_http_request_header("tracestate", None);
+        }
+        let baggage = headers.get("baggage").cloned();
+
+        // Sort rules by priority (lower number = higher priority)
+        let mut sorted_rules = self.config.rules.clone();
+        sorted_rules.sort_by_key(|rule| rule.priority);
+
+        self.dispatch = Some(RouteDispatch {
+            rules: sorted_rules,
+            cursor: 0,
+            headers,
+            cookies,
+            current_path,
+            operation: OperationState::Pending,
+            baggage,
+            candidate_sticky_cookie: None,
+        });
+
+        self.drive_routing()
     }
 
     fn on_http_response_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
+        self.apply_security_headers();
+
         self.add_http_response_header("X-Smart-Router", "active");
+
+        if let Some((name, value)) = self.pending_sticky_cookie.take() {
+            self.add_http_response_header("set-cookie", &format!("{}={}; Path=/", name, value));
+        }
+
         Action::Continue
     }
 }
@@ -219,6 +625,51 @@ proxy_wasm::main! {{
 // Helper Functions
 // =============================================================================
 
+/// What `on_grpc_call_response` should do once a decision-service call for a
+/// rule has completed, decided purely from the call's outcome - no
+/// Context/host calls, so this is unit-testable without a wasm host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GrpcDecisionOutcome {
+    /// The rule is allowed to fire; finalize routing with it.
+    Allow,
+    /// The call failed and `failure_mode` says to fail closed; deny the
+    /// request outright.
+    DenyRequest,
+    /// The call succeeded but the service said no for this rule; advance
+    /// the cursor and keep evaluating later rules.
+    TryNextRule,
+}
+
+/// Decide the outcome of a completed decision-service call. `status_code`
+/// is the gRPC status Envoy reports (`0` = OK); `response_first_byte` is the
+/// first byte of the response body on a successful call, where `1` means
+/// allow and any other byte (or an empty body) means deny.
+fn decide_grpc_outcome(
+    status_code: u32,
+    failure_mode: FailureMode,
+    response_first_byte: Option<u8>,
+) -> GrpcDecisionOutcome {
+    if status_code != 0 {
+        return match failure_mode {
+            FailureMode::Allow => GrpcDecisionOutcome::Allow,
+            FailureMode::Deny => GrpcDecisionOutcome::DenyRequest,
+        };
+    }
+    if response_first_byte == Some(1) {
+        GrpcDecisionOutcome::Allow
+    } else {
+        GrpcDecisionOutcome::TryNextRule
+    }
+}
+
+/// Whether `drive_routing`'s return value means the paused request must be
+/// resumed here. `Action::Pause` means a later rule dispatched another
+/// decision call and will resume the request itself once that completes -
+/// resuming now would double-resume it.
+fn should_resume_after_drive(action: Action) -> bool {
+    matches!(action, Action::Continue)
+}
+
 fn parse_cookies(cookie_header: &str) -> HashMap<String, String> {
     let mut cookies = HashMap::new();
     for cookie in cookie_header.split(';') {
@@ -272,3 +723,388 @@ fn evaluate_conditions(
     }
     true // All conditions matched
 }
+
+/// A parsed or freshly generated W3C Trace Context (`traceparent` header,
+/// version `00` only).
+#[derive(Debug, Clone)]
+struct TraceContext {
+    /// 32 lowercase hex characters.
+    trace_id: String,
+    /// 16 lowercase hex characters.
+    span_id: String,
+    sampled: bool,
+}
+
+impl TraceContext {
+    fn to_header(&self) -> String {
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, if self.sampled { "01" } else { "00" })
+    }
+}
+
+/// Parse a `traceparent` header value, returning `None` for anything that
+/// isn't a well-formed version-00 value (unknown version, wrong-length or
+/// non-hex ids, or an all-zero trace-id/span-id) so the caller regenerates
+/// a fresh trace rather than propagating a malformed one.
+fn parse_traceparent(value: &str) -> Option<TraceContext> {
+    let parts: Vec<&str> = value.split('-').collect();
+    if parts.len() != 4 {
+        return None;
+    }
+    let (version, trace_id, span_id, flags) = (parts[0], parts[1], parts[2], parts[3]);
+
+    if version != "00" {
+        return None;
+    }
+    if trace_id.len() != 32 || !is_lowercase_hex(trace_id) || trace_id.chars().all(|c| c == '0') {
+        return None;
+    }
+    if span_id.len() != 16 || !is_lowercase_hex(span_id) || span_id.chars().all(|c| c == '0') {
+        return None;
+    }
+    if flags.len() != 2 || !is_lowercase_hex(flags) {
+        return None;
+    }
+
+    let flags_byte = u8::from_str_radix(flags, 16).ok()?;
+    Some(TraceContext {
+        trace_id: trace_id.to_string(),
+        span_id: span_id.to_string(),
+        sampled: flags_byte & 0x01 != 0,
+    })
+}
+
+fn is_lowercase_hex(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+/// Generate a fresh trace-id/span-id pair for a request with no (valid)
+/// incoming trace context, sampled by default. Entropy comes from the host
+/// clock mixed with the context id, the same approach [`generate_sticky_id`]
+/// uses for sticky bucketing, with the span-id re-mixed through the FNV
+/// hash so it isn't a visible truncation of the trace-id.
+fn generate_trace_context(context_id: u32, now: SystemTime) -> TraceContext {
+    let nanos = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(std::time::Duration::ZERO)
+        .as_nanos();
+
+    let mut trace_bytes = nanos.to_be_bytes();
+    for (i, b) in context_id.to_be_bytes().iter().enumerate() {
+        trace_bytes[12 + i] ^= b;
+    }
+
+    let span_seed = fnv1a_hash64(&trace_bytes) ^ (context_id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    let span_bytes = span_seed.to_be_bytes();
+
+    TraceContext {
+        trace_id: trace_bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        span_id: span_bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        sampled: true,
+    }
+}
+
+/// W3C Baggage spec limit: a `baggage` header must not exceed 8192 bytes.
+const MAX_BAGGAGE_BYTES: usize = 8192;
+
+/// Merge the routing decision into `existing`'s baggage members as
+/// `router.target`/`router.rule`, replacing any prior members of the same
+/// name, and drop the oldest other members first if the result would
+/// exceed [`MAX_BAGGAGE_BYTES`].
+fn build_baggage(existing: Option<&str>, target: &str, reason: &str) -> String {
+    let mut members: Vec<String> = existing
+        .map(|header| {
+            header
+                .split(',')
+                .map(|member| member.trim().to_string())
+                .filter(|member| !member.is_empty())
+                .filter(|member| !member.starts_with("router.target=") && !member.starts_with("router.rule="))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    members.push(format!("router.target={}", percent_encode_baggage_value(target)));
+    members.push(format!("router.rule={}", percent_encode_baggage_value(reason)));
+
+    while members.join(",").len() > MAX_BAGGAGE_BYTES && members.len() > 2 {
+        members.remove(0);
+    }
+
+    let mut joined = members.join(",");
+    if joined.len() > MAX_BAGGAGE_BYTES {
+        joined.truncate(MAX_BAGGAGE_BYTES);
+    }
+    joined
+}
+
+/// Percent-encode a baggage member value: unreserved characters pass
+/// through, everything else (including `,`, `;`, `=`) is escaped.
+fn percent_encode_baggage_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+/// 64-bit FNV-1a hash, used to deterministically bucket a sticky value into
+/// `0..100` for canary weighting: the same input always yields the same
+/// bucket, so a caller never flips between targets across requests.
+fn fnv1a_hash64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+/// Generate a fresh identifier for a caller with no existing sticky value,
+/// formatted as a UUIDv4 so it's indistinguishable from a client-supplied
+/// one. Entropy comes from the host clock mixed with the context id, which
+/// is sufficient to avoid collisions between concurrently pinned callers
+/// without pulling in a crate for WASM-side randomness.
+fn generate_sticky_id(context_id: u32, now: SystemTime) -> String {
+    let nanos = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(std::time::Duration::ZERO)
+        .as_nanos();
+
+    let mut bytes = nanos.to_be_bytes().to_vec();
+    for (i, b) in context_id.to_be_bytes().iter().enumerate() {
+        bytes[12 + i] ^= b;
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; // version 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // variant 1
+
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        bytes[0], bytes[1], bytes[2], bytes[3],
+        bytes[4], bytes[5],
+        bytes[6], bytes[7],
+        bytes[8], bytes[9],
+        bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15],
+    )
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a_hash64_is_deterministic_and_bucket_stays_in_range() {
+        assert_eq!(fnv1a_hash64(b"user-123"), fnv1a_hash64(b"user-123"));
+        assert_ne!(fnv1a_hash64(b"user-123"), fnv1a_hash64(b"user-124"));
+
+        for value in ["a", "user-123", "", "canary-bucket-test"] {
+            let bucket = fnv1a_hash64(value.as_bytes()) % 100;
+            assert!(bucket < 100);
+        }
+    }
+
+    #[test]
+    fn test_generate_sticky_id_is_a_well_formed_uuidv4() {
+        let id = generate_sticky_id(7, UNIX_EPOCH + Duration::from_nanos(123_456_789));
+        let parts: Vec<&str> = id.split('-').collect();
+        assert_eq!(parts.len(), 5);
+        assert_eq!([parts[0].len(), parts[1].len(), parts[2].len(), parts[3].len(), parts[4].len()], [8, 4, 4, 4, 12]);
+        assert_eq!(&parts[2][0..1], "4"); // version 4
+        assert!(matches!(parts[3].chars().next(), Some('8') | Some('9') | Some('a') | Some('b')));
+    }
+
+    #[test]
+    fn test_generate_sticky_id_differs_across_context_ids() {
+        let now = UNIX_EPOCH + Duration::from_nanos(42);
+        assert_ne!(generate_sticky_id(1, now), generate_sticky_id(2, now));
+    }
+
+    #[test]
+    fn test_parse_cookies_trims_and_lowercases_keys() {
+        let cookies = parse_cookies("  Session=abc123 ; Theme = dark");
+        assert_eq!(cookies.get("session").map(String::as_str), Some("abc123"));
+        assert_eq!(cookies.get("theme").map(String::as_str), Some("dark"));
+    }
+
+    #[test]
+    fn test_evaluate_conditions_equals_and_contains() {
+        let headers = HashMap::from([("x-env".to_string(), "canary-west".to_string())]);
+        let cookies = HashMap::new();
+
+        let equals = vec![Condition {
+            condition_type: "header".to_string(),
+            key: "x-env".to_string(),
+            operator: "equals".to_string(),
+            value: "canary-west".to_string(),
+        }];
+        assert!(evaluate_conditions(&equals, &headers, &cookies));
+
+        let contains = vec![Condition {
+            condition_type: "header".to_string(),
+            key: "x-env".to_string(),
+            operator: "contains".to_string(),
+            value: "canary".to_string(),
+        }];
+        assert!(evaluate_conditions(&contains, &headers, &cookies));
+
+        let mismatch = vec![Condition {
+            condition_type: "header".to_string(),
+            key: "x-env".to_string(),
+            operator: "equals".to_string(),
+            value: "prod".to_string(),
+        }];
+        assert!(!evaluate_conditions(&mismatch, &headers, &cookies));
+    }
+
+    #[test]
+    fn test_evaluate_conditions_unknown_type_and_operator_fail_closed() {
+        let headers = HashMap::from([("x-env".to_string(), "canary".to_string())]);
+        let cookies = HashMap::new();
+
+        let unknown_type = vec![Condition {
+            condition_type: "query".to_string(),
+            key: "x-env".to_string(),
+            operator: "equals".to_string(),
+            value: "canary".to_string(),
+        }];
+        assert!(!evaluate_conditions(&unknown_type, &headers, &cookies));
+
+        let unknown_operator = vec![Condition {
+            condition_type: "header".to_string(),
+            key: "x-env".to_string(),
+            operator: "startswith".to_string(),
+            value: "canary".to_string(),
+        }];
+        assert!(!evaluate_conditions(&unknown_operator, &headers, &cookies));
+    }
+
+    #[test]
+    fn test_parse_traceparent_accepts_well_formed_header() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let parsed = parse_traceparent(header).expect("valid traceparent should parse");
+        assert_eq!(parsed.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parsed.span_id, "00f067aa0ba902b7");
+        assert!(parsed.sampled);
+        assert_eq!(parsed.to_header(), header);
+    }
+
+    #[test]
+    fn test_parse_traceparent_rejects_malformed_headers() {
+        // Wrong number of dash-separated fields.
+        assert!(parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7").is_none());
+        // Unsupported version.
+        assert!(parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        // Wrong-length trace-id.
+        assert!(parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01").is_none());
+        // Wrong-length span-id.
+        assert!(parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902-01").is_none());
+        // Non-hex characters.
+        assert!(parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e473g-00f067aa0ba902b7-01").is_none());
+        // All-zero trace-id.
+        assert!(parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        // All-zero span-id.
+        assert!(parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01").is_none());
+        // Malformed flags.
+        assert!(parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-zz").is_none());
+    }
+
+    #[test]
+    fn test_generate_trace_context_is_well_formed_and_sampled() {
+        let ctx = generate_trace_context(3, UNIX_EPOCH + Duration::from_nanos(987_654_321));
+        assert_eq!(ctx.trace_id.len(), 32);
+        assert_eq!(ctx.span_id.len(), 16);
+        assert!(is_lowercase_hex(&ctx.trace_id));
+        assert!(is_lowercase_hex(&ctx.span_id));
+        assert!(ctx.sampled);
+        assert_eq!(ctx.to_header(), format!("00-{}-{}-01", ctx.trace_id, ctx.span_id));
+    }
+
+    #[test]
+    fn test_percent_encode_baggage_value_escapes_reserved_characters() {
+        assert_eq!(percent_encode_baggage_value("v2"), "v2");
+        assert_eq!(percent_encode_baggage_value("a-b_c.d~e"), "a-b_c.d~e");
+        assert_eq!(percent_encode_baggage_value("a,b;c=d"), "a%2Cb%3Bc%3Dd");
+        assert_eq!(percent_encode_baggage_value(" "), "%20");
+    }
+
+    #[test]
+    fn test_build_baggage_merges_and_replaces_prior_router_members() {
+        let baggage = build_baggage(Some("userId=alice,router.target=v1,router.rule=old"), "v2", "canary");
+        let members: Vec<&str> = baggage.split(',').collect();
+        assert!(members.contains(&"userId=alice"));
+        assert!(members.contains(&"router.target=v2"));
+        assert!(members.contains(&"router.rule=canary"));
+        assert_eq!(members.iter().filter(|m| m.starts_with("router.target=")).count(), 1);
+    }
+
+    #[test]
+    fn test_build_baggage_evicts_oldest_members_once_over_the_limit() {
+        let existing: Vec<String> = (0..50)
+            .map(|i| format!("member{:03}={}", i, "x".repeat(200)))
+            .collect();
+        let baggage = build_baggage(Some(&existing.join(",")), "v2", "canary");
+
+        assert!(baggage.len() <= MAX_BAGGAGE_BYTES);
+        assert!(baggage.contains("router.target=v2"));
+        assert!(baggage.contains("router.rule=canary"));
+        // The oldest members should have been evicted to make room.
+        assert!(!baggage.contains("member000="));
+    }
+
+    #[test]
+    fn test_decide_grpc_outcome_allows_on_successful_allow_byte() {
+        assert_eq!(
+            decide_grpc_outcome(0, FailureMode::Deny, Some(1)),
+            GrpcDecisionOutcome::Allow
+        );
+    }
+
+    #[test]
+    fn test_decide_grpc_outcome_tries_next_rule_on_successful_deny_byte() {
+        assert_eq!(
+            decide_grpc_outcome(0, FailureMode::Deny, Some(0)),
+            GrpcDecisionOutcome::TryNextRule
+        );
+        assert_eq!(
+            decide_grpc_outcome(0, FailureMode::Deny, None),
+            GrpcDecisionOutcome::TryNextRule
+        );
+    }
+
+    #[test]
+    fn test_decide_grpc_outcome_honors_failure_mode_on_error_status() {
+        assert_eq!(
+            decide_grpc_outcome(2, FailureMode::Allow, None),
+            GrpcDecisionOutcome::Allow
+        );
+        assert_eq!(
+            decide_grpc_outcome(2, FailureMode::Deny, None),
+            GrpcDecisionOutcome::DenyRequest
+        );
+    }
+
+    #[test]
+    fn test_decide_grpc_outcome_ignores_response_byte_on_error_status() {
+        // An error status is decided purely by failure_mode, even if a
+        // response body happened to come back with an allow-shaped byte.
+        assert_eq!(
+            decide_grpc_outcome(2, FailureMode::Deny, Some(1)),
+            GrpcDecisionOutcome::DenyRequest
+        );
+    }
+
+    #[test]
+    fn test_should_resume_after_drive_only_on_continue() {
+        assert!(should_resume_after_drive(Action::Continue));
+        assert!(!should_resume_after_drive(Action::Pause));
+    }
+}